@@ -1,10 +1,21 @@
+use std::collections::HashSet;
 use std::env;
 use std::fs;
 use std::io::Write;
 use std::path::Path;
 
+// Words that can't be used as a module name. Directories with these names get a prefix instead.
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "gen", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut",
+    "pub", "ref", "return", "self", "static", "struct", "super", "trait", "true", "type", "unsafe",
+    "use", "where", "while", "abstract", "become", "box", "do", "final", "macro", "override",
+    "priv", "try", "typeof", "unsized", "virtual", "yield",
+];
+
 fn main() {
     // Tell Cargo that if the contents of scripts/ changes, rerun this build script.
+    // Cargo scans directories recursively, so this covers scripts in subdirectories too.
     println!("cargo::rerun-if-changed=scripts/");
 
     let out_dir = env::var("OUT_DIR").expect("OUT_DIR not set");
@@ -14,44 +25,93 @@ fn main() {
 
     let scripts_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("scripts");
 
-    // Read all entries in the scripts directory
-    let entries = fs::read_dir(&scripts_dir).expect("Failed to read scripts directory");
+    write_tests_for_dir(&mut test_file, &scripts_dir, 0);
+}
+
+// Writes a test function for every .fellow file in `dir`, and a nested module for every
+// subdirectory, so that scripts/graphs/bfs.fellow becomes the test graphs::script_bfs.
+fn write_tests_for_dir(test_file: &mut fs::File, dir: &Path, depth: usize) {
+    let indent = "    ".repeat(depth);
+
+    // Read all entries in the directory, sorted so that the generated file (and the suffixes
+    // used to deduplicate names) don't depend on the order the filesystem returns them in.
+    let mut paths: Vec<_> = fs::read_dir(dir)
+        .expect("Failed to read scripts directory")
+        .map(|entry| entry.expect("Failed to read directory entry").path())
+        .collect();
+    paths.sort();
+
+    // Functions and modules live in different namespaces, so they can be deduplicated separately
+    let mut test_fn_names = HashSet::new();
+    let mut module_names = HashSet::new();
 
-    for entry in entries {
-        let entry = entry.expect("Failed to read directory entry");
-        let path = entry.path();
+    for path in paths {
+        let file_name = path.file_name().unwrap().to_string_lossy();
 
-        // Only process files
-        if path.is_file() {
-            let file_name = path.file_name().unwrap().to_string_lossy();
+        if path.is_dir() {
+            let mut module_name = to_identifier(&file_name);
+            if module_name.is_empty()
+                || module_name.starts_with(|c: char| c.is_ascii_digit())
+                || RUST_KEYWORDS.contains(&module_name.as_str())
+            {
+                module_name = format!("dir_{}", module_name);
+            }
+            let module_name = deduplicate(&mut module_names, module_name);
 
+            writeln!(test_file, "\n{}mod {} {{", indent, module_name)
+                .expect("Failed to write test module");
+            write_tests_for_dir(test_file, &path, depth + 1);
+            writeln!(test_file, "{}}}", indent).expect("Failed to write test module");
+        } else if let Some(script_name) = file_name.strip_suffix(".fellow") {
             // Create a valid Rust identifier for the test function name
-            let test_fn_name = format!(
-                "script_{}",
-                file_name
-                    .strip_suffix(".fellow")
-                    .unwrap()
-                    .replace(".", "_")
-                    .replace("-", "_")
-                    .replace(" ", "_")
-                    .to_lowercase()
-                    .trim_end_matches('_') // Remove trailing underscores from original extension
+            let test_fn_name = deduplicate(
+                &mut test_fn_names,
+                format!("script_{}", to_identifier(script_name)),
             );
             // Write the formatted test function string to the file
-            // // Define the path to the script file. Using r#"{}"# inside r## "{}" ##
+            // Define the path to the script file. Using r#"{}"# inside r## "{}" ##
             // correctly handles potential quotes or backslashes in the path.
             writeln!(
                 test_file,
                 r##"
-#[test]
-fn {}() {{
-    let script_path = std::path::Path::new(r#"{}"#);
-    run_single_script_test(script_path);
-}}"##,
+{indent}#[test]
+{indent}fn {}() {{
+{indent}    let script_path = std::path::Path::new(r#"{}"#);
+{indent}    crate::run_single_script_test(script_path);
+{indent}}}"##,
                 test_fn_name,   // Fills the first {} (function name)
                 path.display()  // Fills the second {} (script path)
             )
             .expect("Failed to write test function");
         }
+        // Anything else in scripts/, like a README, isn't a test
+    }
+}
+
+// Lowercases a file name and replaces anything that can't appear in an identifier with `_`
+fn to_identifier(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>()
+        .trim_end_matches('_')
+        .to_string()
+}
+
+// Different file names can end up with the same identifier (e.g. "a-b" and "a_b"), so later ones
+// get a numbered suffix.
+fn deduplicate(used: &mut HashSet<String>, name: String) -> String {
+    let mut candidate = name.clone();
+    let mut suffix = 2;
+    while used.contains(&candidate) {
+        candidate = format!("{}_{}", name, suffix);
+        suffix += 1;
     }
+    used.insert(candidate.clone());
+    candidate
 }
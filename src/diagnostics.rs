@@ -0,0 +1,540 @@
+use std::error::Error;
+use std::fmt::Write;
+
+use serde_json::{Value, json};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::scanner::is_identifier_grapheme;
+use crate::{FellowError, ScanErrorKind};
+
+// How many columns a tab is drawn as when showing a source line
+const TAB_WIDTH: usize = 4;
+
+/// A range of graphemes in the source code, using the same offsets as `TokenContext::start` and
+/// `TokenContext::end`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+        }
+    }
+}
+
+/// A message attached to a part of the source code. The primary label points at the cause of
+/// the diagnostic, secondary labels add context.
+#[derive(Debug)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+/// An edit that might fix the problem: replace the source code in `span` with `replacement`.
+/// An empty span inserts the replacement, an empty replacement deletes the span.
+#[derive(Debug)]
+pub struct Suggestion {
+    pub message: String,
+    pub span: Span,
+    pub replacement: String,
+}
+
+/// Everything needed to show an error to a person, independently of how it gets rendered.
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub primary: Option<Label>,
+    pub secondary: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Vec<String>,
+    pub suggestions: Vec<Suggestion>,
+}
+
+impl Diagnostic {
+    fn error(code: &'static str, message: String) -> Self {
+        Self {
+            severity: Severity::Error,
+            code,
+            message,
+            primary: None,
+            secondary: Vec::new(),
+            notes: Vec::new(),
+            help: Vec::new(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    pub fn from_error(err: &FellowError) -> Self {
+        let mut diagnostic = Self::error(err.code(), err.to_string());
+        if let FellowError::Runtime(runtime_error) = err {
+            diagnostic.message = runtime_error.kind().to_string();
+            diagnostic.primary = runtime_error.span().map(|span| Label {
+                span,
+                message: String::new(),
+            });
+        }
+        if let FellowError::Scan(scan_error) = err {
+            diagnostic.message = scan_error.kind().to_string();
+            let span = scan_error.span();
+            match scan_error.kind() {
+                ScanErrorKind::UnexpectedCharacter(c) => {
+                    diagnostic.primary = Some(Label {
+                        span,
+                        message: "this isn't the start of any token".to_string(),
+                    });
+                    // Removing part of a word wouldn't help, since the rest of it would still
+                    // fail to scan
+                    if is_identifier_grapheme(c) {
+                        diagnostic.notes.push(
+                            "the scanner doesn't recognize identifiers or keywords yet".to_string(),
+                        );
+                    } else {
+                        diagnostic.suggestions.push(Suggestion {
+                            message: format!("remove `{}`", c.escape_debug()),
+                            span,
+                            replacement: String::new(),
+                        });
+                    }
+                }
+                ScanErrorKind::UnterminatedString => {
+                    diagnostic.primary = Some(Label {
+                        span,
+                        message: "this string is never closed".to_string(),
+                    });
+                    diagnostic
+                        .help
+                        .push("add a `\"` where the string should end".to_string());
+                }
+                ScanErrorKind::InvalidInteger(reason) => {
+                    diagnostic.primary = Some(Label {
+                        span,
+                        message: reason.clone(),
+                    });
+                    diagnostic.notes.push(format!(
+                        "integers are 64-bit, between {} and {}",
+                        i64::MIN,
+                        i64::MAX
+                    ));
+                }
+            }
+        }
+
+        let mut source = err.source();
+        while let Some(cause) = source {
+            diagnostic.notes.push(format!("caused by: {}", cause));
+            source = cause.source();
+        }
+        diagnostic
+    }
+
+    /// Renders the diagnostic in the style of rustc, quoting the lines of `source` that the
+    /// labels point at. `color` adds ANSI escape codes for terminals.
+    pub fn render(&self, file_name: &str, source: &str, color: bool) -> String {
+        let style = Style { color };
+        let mut out = String::new();
+
+        let severity_color = match self.severity {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+        };
+        let _ = writeln!(
+            out,
+            "{}{}",
+            style.paint(
+                &format!("{}[{}]", self.severity.name(), self.code),
+                severity_color
+            ),
+            style.paint(&format!(": {}", self.message), BOLD)
+        );
+
+        let graphemes: Vec<&str> = source.graphemes(true).collect();
+        let lines = SourceLines::new(&graphemes);
+
+        let mut labels: Vec<(&Label, bool)> = Vec::new();
+        if let Some(primary) = &self.primary {
+            labels.push((primary, true));
+        }
+        labels.extend(self.secondary.iter().map(|label| (label, false)));
+
+        // The gutter is wide enough for the largest line number that gets printed
+        let gutter_width = labels
+            .iter()
+            .map(|(label, _)| (lines.line_of(label.span.start) + 1).to_string().len())
+            .max()
+            .unwrap_or(0);
+        let gutter = " ".repeat(gutter_width);
+
+        if let Some((first, _)) = labels.first() {
+            let line = lines.line_of(first.span.start);
+            let column = first.span.start - lines.start_of(line);
+            let _ = writeln!(
+                out,
+                "{}{} {}:{}:{}",
+                gutter,
+                style.paint("-->", BLUE),
+                file_name,
+                line + 1,
+                column + 1
+            );
+            let _ = writeln!(out, "{} {}", gutter, style.paint("|", BLUE));
+        }
+
+        // Each line is quoted once, followed by an underline for every label on it
+        let mut quoted_lines: Vec<usize> = labels
+            .iter()
+            .map(|(label, _)| lines.line_of(label.span.start))
+            .collect();
+        quoted_lines.sort();
+        quoted_lines.dedup();
+        for line in quoted_lines {
+            let line_graphemes = lines.graphemes_of(line);
+            let text: String = line_graphemes.iter().map(|g| display(g)).collect();
+            let _ = writeln!(
+                out,
+                "{} {} {}",
+                style.paint(&format!("{:>gutter_width$}", line + 1), BLUE),
+                style.paint("|", BLUE),
+                text.trim_end()
+            );
+
+            for (label, is_primary) in &labels {
+                if lines.line_of(label.span.start) != line {
+                    continue;
+                }
+                // Spans that continue onto later lines are only underlined up to the end of the
+                // first line.
+                let line_start = lines.start_of(line);
+                let line_end = line_start + line_graphemes.len();
+                let start = label.span.start - line_start;
+                let end = label.span.end.min(line_end).max(label.span.start) - line_start;
+                let indent = width(&line_graphemes[..start]);
+                let length = width(&line_graphemes[start..end.min(line_graphemes.len())]).max(1);
+
+                let (marker, marker_color) = if *is_primary {
+                    ("^", severity_color)
+                } else {
+                    ("-", BLUE)
+                };
+                let underline = format!("{} {}", marker.repeat(length), label.message);
+                let _ = writeln!(
+                    out,
+                    "{} {} {}{}",
+                    gutter,
+                    style.paint("|", BLUE),
+                    " ".repeat(indent),
+                    style.paint(underline.trim_end(), marker_color)
+                );
+            }
+        }
+
+        let has_footer =
+            !self.notes.is_empty() || !self.help.is_empty() || !self.suggestions.is_empty();
+        if !labels.is_empty() && has_footer {
+            let _ = writeln!(out, "{} {}", gutter, style.paint("|", BLUE));
+        }
+        for note in &self.notes {
+            let _ = writeln!(out, "{} {} note: {}", gutter, style.paint("=", BLUE), note);
+        }
+        let suggestions = self.suggestions.iter().map(|s| &s.message);
+        for help in self.help.iter().chain(suggestions) {
+            let _ = writeln!(out, "{} {} help: {}", gutter, style.paint("=", BLUE), help);
+        }
+        out
+    }
+
+    /// The diagnostic as a JSON object, for tools that show errors in their own UI. Lines and
+    /// columns count from 1 like the human-readable output. Offsets are given both in graphemes,
+    /// like the rest of Fellow, and in bytes, which is what most editors work with.
+    pub fn to_json(&self, file_name: &str, source: &str) -> Value {
+        let graphemes: Vec<&str> = source.graphemes(true).collect();
+        let lines = SourceLines::new(&graphemes);
+
+        let mut spans = Vec::new();
+        if let Some(primary) = &self.primary {
+            spans.push(span_json(
+                file_name,
+                &lines,
+                primary.span,
+                Some(&primary.message),
+                true,
+            ));
+        }
+        for label in &self.secondary {
+            spans.push(span_json(
+                file_name,
+                &lines,
+                label.span,
+                Some(&label.message),
+                false,
+            ));
+        }
+        let suggestions: Vec<Value> = self
+            .suggestions
+            .iter()
+            .map(|suggestion| {
+                json!({
+                    "message": suggestion.message,
+                    "replacement": suggestion.replacement,
+                    "span": span_json(file_name, &lines, suggestion.span, None, false),
+                })
+            })
+            .collect();
+
+        json!({
+            "code": self.code,
+            "severity": self.severity.name(),
+            "message": self.message,
+            "spans": spans,
+            "notes": self.notes,
+            "help": self.help,
+            "suggestions": suggestions,
+        })
+    }
+}
+
+fn span_json(
+    file_name: &str,
+    lines: &SourceLines,
+    span: Span,
+    label: Option<&str>,
+    is_primary: bool,
+) -> Value {
+    let (line_start, column_start) = lines.position_of(span.start);
+    let (line_end, column_end) = lines.position_of(span.end);
+    json!({
+        "file_name": file_name,
+        "is_primary": is_primary,
+        "label": label,
+        "line_start": line_start + 1,
+        "column_start": column_start + 1,
+        "line_end": line_end + 1,
+        "column_end": column_end + 1,
+        "grapheme_start": span.start,
+        "grapheme_end": span.end,
+        "byte_start": lines.byte_offset_of(span.start),
+        "byte_end": lines.byte_offset_of(span.end),
+    })
+}
+
+// The lines of the source code, split the same way the scanner counts them
+struct SourceLines<'a> {
+    graphemes: &'a [&'a str],
+    // The grapheme that each line starts at
+    starts: Vec<usize>,
+}
+
+impl<'a> SourceLines<'a> {
+    fn new(graphemes: &'a [&'a str]) -> Self {
+        let mut starts = vec![0];
+        for (i, grapheme) in graphemes.iter().enumerate() {
+            if *grapheme == "\n" || *grapheme == "\r\n" {
+                starts.push(i + 1);
+            }
+        }
+        Self { graphemes, starts }
+    }
+
+    fn line_of(&self, offset: usize) -> usize {
+        self.starts.partition_point(|start| *start <= offset) - 1
+    }
+
+    fn start_of(&self, line: usize) -> usize {
+        self.starts[line]
+    }
+
+    // The line and column of a grapheme, both counted from 0
+    fn position_of(&self, offset: usize) -> (usize, usize) {
+        let line = self.line_of(offset);
+        (line, offset - self.start_of(line))
+    }
+
+    fn byte_offset_of(&self, offset: usize) -> usize {
+        self.graphemes[..offset.min(self.graphemes.len())]
+            .iter()
+            .map(|g| g.len())
+            .sum()
+    }
+
+    // The graphemes of a line, without its newline
+    fn graphemes_of(&self, line: usize) -> &'a [&'a str] {
+        let start = self.starts[line];
+        let end = match self.starts.get(line + 1) {
+            Some(next) => next - 1,
+            None => self.graphemes.len(),
+        };
+        &self.graphemes[start..end]
+    }
+}
+
+fn display(grapheme: &str) -> String {
+    if grapheme == "\t" {
+        " ".repeat(TAB_WIDTH)
+    } else {
+        grapheme.to_string()
+    }
+}
+
+// The number of terminal columns taken up by some graphemes. Wide graphemes like emoji or CJK
+// characters take up two columns, so the underline has to be longer than the grapheme count.
+fn width(graphemes: &[&str]) -> usize {
+    graphemes
+        .iter()
+        .map(|g| if *g == "\t" { TAB_WIDTH } else { g.width() })
+        .sum()
+}
+
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+struct Style {
+    color: bool,
+}
+
+impl Style {
+    fn paint(&self, text: &str, code: &str) -> String {
+        if self.color {
+            format!("{}{}{}", code, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scan;
+
+    fn render_scan_error(source: &str) -> String {
+        let err = scan(source).err().expect("source should fail to scan");
+        Diagnostic::from_error(&err).render("test.fellow", source, false)
+    }
+
+    #[test]
+    fn only_suggests_removing_characters_outside_identifiers() {
+        let diagnostic = |source| {
+            let err = scan(source).err().expect("source should fail to scan");
+            Diagnostic::from_error(&err)
+        };
+        assert_eq!(diagnostic("1 ?").suggestions.len(), 1);
+        for source in ["abc", "_abc", "e\u{301}"] {
+            let word = diagnostic(source);
+            assert!(word.suggestions.is_empty(), "{:?}", source);
+            assert_eq!(word.notes.len(), 1, "{:?}", source);
+        }
+    }
+
+    #[test]
+    fn renders_unterminated_string() {
+        let source = "42\n  \"never closed\n";
+        assert_eq!(
+            render_scan_error(source),
+            r#"error[F0102]: unterminated string
+ --> test.fellow:2:3
+  |
+2 |   "never closed
+  |   ^ this string is never closed
+  |
+  = help: add a `"` where the string should end
+"#
+        );
+    }
+
+    #[test]
+    fn underlines_wide_graphemes() {
+        // The emoji takes up two columns in a terminal, so the caret under the unexpected
+        // character that follows it has to be indented by four columns rather than three.
+        let source = "\"😀\"?";
+        assert_eq!(
+            render_scan_error(source),
+            r#"error[F0101]: unexpected character `?`
+ --> test.fellow:1:4
+  |
+1 | "😀"?
+  |     ^ this isn't the start of any token
+  |
+  = help: remove `?`
+"#
+        );
+    }
+
+    #[test]
+    fn converts_spans_to_json() {
+        let source = "\"😀\"\n  ?";
+        let err = scan(source).err().expect("source should fail to scan");
+        let json = Diagnostic::from_error(&err).to_json("test.fellow", source);
+        assert_eq!(json["code"], "F0101");
+        assert_eq!(
+            json["spans"][0],
+            json!({
+                "file_name": "test.fellow",
+                "is_primary": true,
+                "label": "this isn't the start of any token",
+                "line_start": 2,
+                "column_start": 3,
+                "line_end": 2,
+                "column_end": 4,
+                "grapheme_start": 6,
+                "grapheme_end": 7,
+                "byte_start": 9,
+                "byte_end": 10,
+            })
+        );
+        assert_eq!(json["suggestions"][0]["replacement"], "");
+    }
+
+    #[test]
+    fn renders_secondary_labels() {
+        let source = "one\ntwo\n";
+        let diagnostic = Diagnostic {
+            severity: Severity::Warning,
+            code: "F9999",
+            message: "example".to_string(),
+            primary: Some(Label {
+                span: Span::new(4, 7),
+                message: "primary".to_string(),
+            }),
+            secondary: vec![Label {
+                span: Span::new(0, 3),
+                message: "secondary".to_string(),
+            }],
+            notes: Vec::new(),
+            help: Vec::new(),
+            suggestions: Vec::new(),
+        };
+        assert_eq!(
+            diagnostic.render("test.fellow", source, false),
+            r#"warning[F9999]: example
+ --> test.fellow:2:1
+  |
+1 | one
+  | --- secondary
+2 | two
+  | ^^^ primary
+"#
+        );
+    }
+}
@@ -0,0 +1,316 @@
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::diagnostics::Span;
+
+/// Every error that Fellow can report, grouped by the stage of the interpreter that found it.
+/// The Display of each variant is the Display of the error it wraps, and `source` continues the
+/// chain from there, so wrapping a FellowError doesn't repeat its message.
+#[derive(Debug)]
+pub enum FellowError {
+    Io(IoError),
+    Scan(ScanError),
+    Runtime(RuntimeError),
+}
+
+impl FellowError {
+    // A short, stable identifier for the kind of error. Scripts in scripts/ refer to these
+    // with `// expect-error: <code>`, and `fellow explain <code>` describes them in detail, so
+    // they shouldn't change once assigned. See error_codes.rs for how they're numbered.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Io(err) => err.operation.code(),
+            Self::Scan(err) => err.kind.code(),
+            Self::Runtime(err) => err.kind.code(),
+        }
+    }
+
+    /// The part of the source code that caused the error. IO errors happen outside of any
+    /// script, so they don't have one.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Self::Io(_) => None,
+            Self::Scan(err) => Some(err.span),
+            Self::Runtime(err) => err.span,
+        }
+    }
+}
+
+impl Error for FellowError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(err) => err.source(),
+            Self::Scan(err) => err.source(),
+            Self::Runtime(err) => err.source(),
+        }
+    }
+}
+
+impl Display for FellowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(err) => err.fmt(f),
+            Self::Scan(err) => err.fmt(f),
+            Self::Runtime(err) => err.fmt(f),
+        }
+    }
+}
+
+impl From<IoError> for FellowError {
+    fn from(err: IoError) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<ScanError> for FellowError {
+    fn from(err: ScanError) -> Self {
+        Self::Scan(err)
+    }
+}
+
+impl From<RuntimeError> for FellowError {
+    fn from(err: RuntimeError) -> Self {
+        Self::Runtime(err)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoOperation {
+    ReadScript,
+    WriteOutput,
+    // Writing a script, like `fellow fmt` and the REPL's :save do
+    WriteScript,
+}
+
+impl IoOperation {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::ReadScript => "F0001",
+            Self::WriteOutput => "F0002",
+            Self::WriteScript => "F0003",
+        }
+    }
+}
+
+/// Reading a script or writing its output failed. The underlying io::Error is the source.
+#[derive(Debug)]
+pub struct IoError {
+    operation: IoOperation,
+    // The file involved, if there is one. Scripts read from stdin don't have a path.
+    path: Option<PathBuf>,
+    source: io::Error,
+}
+
+impl IoError {
+    pub fn new(operation: IoOperation, path: Option<PathBuf>, source: io::Error) -> Self {
+        Self {
+            operation,
+            path,
+            source,
+        }
+    }
+
+    pub fn operation(&self) -> IoOperation {
+        self.operation
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub fn io_error(&self) -> &io::Error {
+        &self.source
+    }
+}
+
+impl Error for IoError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl Display for IoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.operation, &self.path) {
+            (IoOperation::ReadScript, Some(path)) => write!(f, "cannot read {}", path.display()),
+            (IoOperation::ReadScript, None) => write!(f, "cannot read script"),
+            (IoOperation::WriteOutput, _) => write!(f, "cannot write program output"),
+            (IoOperation::WriteScript, Some(path)) => write!(f, "cannot write {}", path.display()),
+            (IoOperation::WriteScript, None) => write!(f, "cannot write script"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScanErrorKind {
+    UnexpectedCharacter(String),
+    UnterminatedString,
+    // The reason that the literal couldn't be parsed as an i64
+    InvalidInteger(String),
+}
+
+impl ScanErrorKind {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::UnexpectedCharacter(_) => "F0101",
+            Self::UnterminatedString => "F0102",
+            Self::InvalidInteger(_) => "F0103",
+        }
+    }
+}
+
+impl Display for ScanErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnexpectedCharacter(c) => {
+                write!(f, "unexpected character `{}`", c.escape_debug())
+            }
+            Self::UnterminatedString => write!(f, "unterminated string"),
+            Self::InvalidInteger(reason) => write!(f, "invalid integer literal: {}", reason),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ScanError {
+    kind: ScanErrorKind,
+    // Where the offending lexeme starts, both counted from 0
+    line: usize,
+    column: usize,
+    span: Span,
+}
+
+impl ScanError {
+    pub(crate) fn new(kind: ScanErrorKind, line: usize, column: usize, span: Span) -> Self {
+        Self {
+            kind,
+            line,
+            column,
+            span,
+        }
+    }
+
+    // Moves an error found in part of a source to where that part starts in the whole source.
+    // Parts start at the beginning of a line, so the column stays the same.
+    pub(crate) fn offset_by(mut self, lines: usize, graphemes: usize) -> Self {
+        self.line += lines;
+        self.span = Span::new(self.span.start + graphemes, self.span.end + graphemes);
+        self
+    }
+
+    pub fn kind(&self) -> &ScanErrorKind {
+        &self.kind
+    }
+
+    /// The line that the offending lexeme starts on, counted from 0
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// The column that the offending lexeme starts at, counted from 0
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Error for ScanError {}
+
+impl Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Fellow scanner error occured at line {}:{}: {}",
+            self.line + 1,
+            self.column + 1,
+            self.kind
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuntimeErrorKind {
+    // The script didn't contain anything with a value
+    NothingToEvaluate,
+}
+
+impl RuntimeErrorKind {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NothingToEvaluate => "F0501",
+        }
+    }
+}
+
+impl Display for RuntimeErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NothingToEvaluate => write!(f, "nothing to evaluate"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RuntimeError {
+    kind: RuntimeErrorKind,
+    // Where in the script the error happened, if it can be pinned down
+    span: Option<Span>,
+}
+
+impl RuntimeError {
+    pub(crate) fn new(kind: RuntimeErrorKind, span: Option<Span>) -> Self {
+        Self { kind, span }
+    }
+
+    pub fn kind(&self) -> &RuntimeErrorKind {
+        &self.kind
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
+}
+
+impl Error for RuntimeError {}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Fellow runtime error: {}", self.kind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scan;
+
+    #[test]
+    fn io_errors_keep_their_cause() {
+        let cause = io::Error::new(io::ErrorKind::NotFound, "no such file");
+        let err = FellowError::from(IoError::new(
+            IoOperation::ReadScript,
+            Some(PathBuf::from("missing.fellow")),
+            cause,
+        ));
+        assert_eq!(err.to_string(), "cannot read missing.fellow");
+        assert_eq!(err.code(), "F0001");
+        assert_eq!(err.span(), None);
+        let source = err.source().expect("IO errors should have a source");
+        assert_eq!(source.to_string(), "no such file");
+    }
+
+    #[test]
+    fn scan_errors_expose_their_position() {
+        let Err(FellowError::Scan(err)) = scan("42\n  \"open") else {
+            panic!("source should fail to scan");
+        };
+        assert_eq!(err.kind(), &ScanErrorKind::UnterminatedString);
+        assert_eq!((err.line(), err.column()), (1, 2));
+        assert_eq!(err.span(), Span::new(5, 6));
+    }
+}
@@ -0,0 +1,95 @@
+// Long-form explanations for every error code, shown by `fellow explain <code>`. Each one lives
+// in its own Markdown file in src/error_codes/ so they're easy to write and review.
+//
+// Codes are grouped by the stage of the interpreter that reports them:
+// * F00xx - reading and writing files
+// * F01xx - scanning
+// * F02xx - parsing
+// * F03xx - name resolution
+// * F04xx - type checking
+// * F05xx - running the program
+//
+// Once a code has been released it must keep its meaning. Retired codes stay in this list so that
+// old error messages can still be explained.
+const EXPLANATIONS: &[(&str, &str)] = &[
+    ("F0001", include_str!("error_codes/F0001.md")),
+    ("F0002", include_str!("error_codes/F0002.md")),
+    ("F0003", include_str!("error_codes/F0003.md")),
+    ("F0101", include_str!("error_codes/F0101.md")),
+    ("F0102", include_str!("error_codes/F0102.md")),
+    ("F0103", include_str!("error_codes/F0103.md")),
+    ("F0501", include_str!("error_codes/F0501.md")),
+];
+
+/// The explanation for an error code. Codes are matched case-insensitively and the leading `F`
+/// is optional, so `F0101`, `f0101` and `0101` all find the same explanation.
+pub fn explain(code: &str) -> Option<&'static str> {
+    let code = code.trim();
+    let code = code.strip_prefix(['F', 'f']).unwrap_or(code);
+    EXPLANATIONS
+        .iter()
+        .find(|(known, _)| known[1..] == *code)
+        .map(|(_, explanation)| *explanation)
+}
+
+/// Every error code that has an explanation, in order
+pub fn codes() -> impl Iterator<Item = &'static str> {
+    EXPLANATIONS.iter().map(|(code, _)| *code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{IoOperation, RuntimeErrorKind, ScanErrorKind};
+
+    // The code of every kind of error. Each kind goes through a match without a wildcard, so
+    // adding a variant stops this from compiling until the new kind is listed here too.
+    fn every_code() -> Vec<&'static str> {
+        let mut codes = Vec::new();
+        let operations = [
+            IoOperation::ReadScript,
+            IoOperation::WriteOutput,
+            IoOperation::WriteScript,
+        ];
+        for operation in operations {
+            match operation {
+                IoOperation::ReadScript | IoOperation::WriteOutput | IoOperation::WriteScript => {
+                    codes.push(operation.code())
+                }
+            }
+        }
+        let scan_kinds = [
+            ScanErrorKind::UnexpectedCharacter(String::new()),
+            ScanErrorKind::UnterminatedString,
+            ScanErrorKind::InvalidInteger(String::new()),
+        ];
+        for kind in scan_kinds {
+            match kind {
+                ScanErrorKind::UnexpectedCharacter(_)
+                | ScanErrorKind::UnterminatedString
+                | ScanErrorKind::InvalidInteger(_) => codes.push(kind.code()),
+            }
+        }
+        for kind in [RuntimeErrorKind::NothingToEvaluate] {
+            match kind {
+                RuntimeErrorKind::NothingToEvaluate => codes.push(kind.code()),
+            }
+        }
+        codes
+    }
+
+    #[test]
+    fn every_error_has_an_explanation() {
+        for code in every_code() {
+            assert!(explain(code).is_some(), "{} has no explanation", code);
+        }
+    }
+
+    #[test]
+    fn explains_codes_without_the_prefix() {
+        assert_eq!(explain("f0101"), explain("F0101"));
+        assert_eq!(explain("0101"), explain("F0101"));
+        assert_eq!(explain("F9999"), None);
+    }
+}
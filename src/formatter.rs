@@ -0,0 +1,312 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{FellowError, ScanErrorKind, Token, TokenContext, scan};
+
+const INDENT: &str = "    ";
+
+// A line of the source code, or several lines when a string spans them
+enum Line {
+    // A line that the scanner recognizes, without its whitespace
+    Code(Vec<TokenContext>),
+    // A line that isn't code is prose, which is an implicit comment. It's kept exactly as it was
+    // written, apart from its line ending.
+    Prose(String),
+}
+
+/// Formats a script. Code gets consistent indentation and spacing, runs of blank lines become
+/// a single blank line, and comments and prose are kept as they were written.
+pub fn format(source: &str) -> Result<String, FellowError> {
+    let mut lines = Vec::new();
+    for chunk in split_lines(source)? {
+        let text = chunk.text;
+        match scan(&text) {
+            Ok(tokens) => lines.push(Line::Code(
+                tokens
+                    .into_iter()
+                    .filter(|tc| !tc.token.is_whitespace())
+                    .collect(),
+            )),
+            Err(FellowError::Scan(err))
+                if matches!(err.kind(), ScanErrorKind::UnexpectedCharacter(_)) =>
+            {
+                let prose = text.strip_suffix('\n').unwrap_or(&text);
+                let prose = prose.strip_suffix('\r').unwrap_or(prose);
+                lines.push(Line::Prose(prose.to_string()))
+            }
+            Err(err) => return Err(chunk.position.relocate(err)),
+        }
+    }
+
+    let mut output: Vec<String> = Vec::new();
+    let mut depth: usize = 0;
+    for line in lines {
+        match line {
+            Line::Prose(prose) => output.push(prose),
+            Line::Code(tokens) if tokens.is_empty() => {
+                // Only keep a blank line if it separates two other lines
+                if output.last().is_some_and(|last| !last.is_empty()) {
+                    output.push(String::new());
+                }
+            }
+            Line::Code(tokens) => {
+                // Closing brackets at the start of a line line up with the line that opened them
+                let leading_closers = tokens.iter().take_while(|tc| is_closer(&tc.token)).count();
+                let indent = INDENT.repeat(depth.saturating_sub(leading_closers));
+                output.push(indent + &format_tokens(&tokens));
+
+                for tc in &tokens {
+                    if is_opener(&tc.token) {
+                        depth += 1;
+                    } else if is_closer(&tc.token) {
+                        depth = depth.saturating_sub(1);
+                    }
+                }
+            }
+        }
+    }
+    while output.last().is_some_and(|last| last.is_empty()) {
+        output.pop();
+    }
+
+    // Files keep the line endings they already use, going by the first one
+    let newline = match source.find('\n') {
+        Some(i) if source[..i].ends_with('\r') => "\r\n",
+        _ => "\n",
+    };
+    if output.is_empty() {
+        Ok(String::new())
+    } else {
+        Ok(output.join(newline) + newline)
+    }
+}
+
+// One or more lines of the source that are scanned together
+struct Chunk {
+    text: String,
+    position: Position,
+}
+
+// Where a chunk starts in the whole source
+#[derive(Clone, Copy)]
+struct Position {
+    line: usize,
+    grapheme: usize,
+}
+
+impl Position {
+    // Errors from scanning a chunk on its own count from the start of the chunk, but they're
+    // reported against the whole source
+    fn relocate(self, err: FellowError) -> FellowError {
+        match err {
+            FellowError::Scan(err) => err.offset_by(self.line, self.grapheme).into(),
+            err => err,
+        }
+    }
+}
+
+// Splits the source into lines, keeping lines together when a string continues from one to the
+// next. Each line still ends with its newline, so that it can be scanned on its own.
+fn split_lines(source: &str) -> Result<Vec<Chunk>, FellowError> {
+    let mut chunks = Vec::new();
+    let mut pending = String::new();
+    let mut start = Position {
+        line: 0,
+        grapheme: 0,
+    };
+    let mut next = start;
+    for line in source.split_inclusive('\n') {
+        pending.push_str(line);
+        next.line += 1;
+        next.grapheme += line.graphemes(true).count();
+        match scan(&pending) {
+            Err(FellowError::Scan(err)) if err.kind() == &ScanErrorKind::UnterminatedString => {}
+            _ => {
+                chunks.push(Chunk {
+                    text: std::mem::take(&mut pending),
+                    position: start,
+                });
+                start = next;
+            }
+        }
+    }
+    // A string that is still open at the end of the file is an error
+    if let Err(err) = scan(&pending) {
+        return Err(start.relocate(err));
+    }
+    Ok(chunks)
+}
+
+fn format_tokens(tokens: &[TokenContext]) -> String {
+    let mut formatted = String::new();
+    let mut previous: Option<&Token> = None;
+    // Whether the previous token was a prefix operator like the `-` in `-1`
+    let mut previous_is_unary = false;
+    for tc in tokens {
+        if let Some(previous) = previous
+            && (would_merge(previous, &tc.token)
+                || !previous_is_unary && space_between(previous, &tc.token))
+        {
+            formatted.push(' ');
+        }
+        match &tc.token {
+            // The lexeme of a comment includes its newline
+            Token::Comment(_) => formatted.push_str(tc.lexeme().trim_end()),
+            _ => formatted.push_str(tc.lexeme()),
+        }
+        previous_is_unary = match &tc.token {
+            Token::Bang => true,
+            Token::Minus => !previous.is_some_and(ends_value),
+            _ => false,
+        };
+        previous = Some(&tc.token);
+    }
+    formatted
+}
+
+// Whether two tokens would scan as a different token if they were written next to each other,
+// like `!` and `=` becoming `!=`. They always need a space between them.
+fn would_merge(previous: &Token, next: &Token) -> bool {
+    matches!(
+        (previous, next),
+        (
+            Token::Bang | Token::Less | Token::Greater | Token::Equal,
+            Token::Equal | Token::EqualEqual
+        ) | (Token::Colon, Token::Colon | Token::ColonColon)
+            | (Token::Slash, Token::Slash)
+    )
+}
+
+fn space_between(previous: &Token, next: &Token) -> bool {
+    match (previous, next) {
+        (_, Token::Comment(_)) => true,
+        (Token::LeftParen, _) | (_, Token::RightParen) => false,
+        (_, Token::Comma | Token::Semicolon) => false,
+        (Token::Dot | Token::ColonColon, _) | (_, Token::Dot | Token::ColonColon) => false,
+        // Type annotations are written `x: Int`
+        (_, Token::Colon) => false,
+        // Calls are written `f(x)`, but keywords keep their space, like `if (x)`
+        (Token::Identifier(_) | Token::RightParen, Token::LeftParen) => false,
+        _ => true,
+    }
+}
+
+// Tokens that can end an operand, so a `-` after one of them is subtraction rather than negation
+fn ends_value(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Identifier(_)
+            | Token::String(_)
+            | Token::Integer(_)
+            | Token::Float(_)
+            | Token::Boolean(_)
+            | Token::True
+            | Token::False
+            | Token::Nil
+            | Token::This
+            | Token::Super
+            | Token::RightParen
+    )
+}
+
+fn is_opener(token: &Token) -> bool {
+    matches!(token, Token::LeftParen | Token::LeftBrace)
+}
+
+fn is_closer(token: &Token) -> bool {
+    matches!(token, Token::RightParen | Token::RightBrace)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::Span;
+
+    // The tokens of a script, without its whitespace and comments
+    fn tokens(source: &str) -> Vec<Token> {
+        scan(source)
+            .unwrap()
+            .into_iter()
+            .filter(|tc| !tc.token.is_whitespace() && !tc.token.is_trivia())
+            .map(|tc| tc.token)
+            .collect()
+    }
+
+    #[test]
+    fn normalizes_spacing_and_indentation() {
+        let source = "{\n1+2\n  {  -3 ,4;}\n\t\t}\n";
+        assert_eq!(format(source).unwrap(), "{\n    1 + 2\n    { -3, 4; }\n}\n");
+    }
+
+    #[test]
+    fn keeps_comments_and_prose() {
+        let source = "\n\n// A comment  \n  Some prose,   spaced out\n\n\n\n1   // trailing\n\n";
+        assert_eq!(
+            format(source).unwrap(),
+            "// A comment\n  Some prose,   spaced out\n\n1 // trailing\n"
+        );
+    }
+
+    #[test]
+    fn keeps_multiline_strings() {
+        let source = "{\n\"first\n   second\"\n}\r\n";
+        assert_eq!(format(source).unwrap(), "{\n    \"first\n   second\"\n}\n");
+    }
+
+    #[test]
+    fn keeps_windows_line_endings() {
+        let source = "{\r\n1+2 // sum\r\n}\r\n";
+        let formatted = format(source).unwrap();
+        assert_eq!(formatted, "{\r\n    1 + 2 // sum\r\n}\r\n");
+        assert_eq!(format(&formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn formatting_twice_changes_nothing() {
+        let source = "( 1 , -  2 )\n{\n!( 3 )==4 // done\n}\n";
+        let formatted = format(source).unwrap();
+        assert_eq!(formatted, "(1, -2)\n{\n    !(3) == 4 // done\n}\n");
+        assert_eq!(format(&formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn keeps_the_same_tokens() {
+        for source in ["! = 1\n", "! == 1\n", "1 : : 2\n", "1 : :: 2\n", "< = >\n"] {
+            assert_eq!(
+                tokens(&format(source).unwrap()),
+                tokens(source),
+                "{:?}",
+                source
+            );
+        }
+        // Every pair of operators and punctuation, both apart and together
+        let lexemes = [
+            "(", ")", "{", "}", ",", ".", "-", "+", ";", "/", "\\", "*", "!", "!=", "=", "==", ">",
+            ">=", "<", "<=", ":", "::", "1", "\"a\"",
+        ];
+        for first in lexemes {
+            for second in lexemes {
+                for source in [
+                    format!("{} {}\n", first, second),
+                    format!("{}{}\n", first, second),
+                ] {
+                    let formatted = format(&source).unwrap();
+                    assert_eq!(tokens(&formatted), tokens(&source), "{:?}", source);
+                    assert_eq!(format(&formatted).unwrap(), formatted, "{:?}", source);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn reports_errors_where_they_are_in_the_file() {
+        let position = |source| match format(source) {
+            Err(FellowError::Scan(err)) => (err.line(), err.column(), err.span()),
+            _ => panic!("{:?} should fail to format", source),
+        };
+        assert_eq!(position("1\n2\n  \"open\n"), (2, 2, Span::new(6, 7)));
+        assert_eq!(
+            position("1\n\"two\nlines\"\n99999999999999999999999\n"),
+            (3, 0, Span::new(14, 37))
+        );
+    }
+}
@@ -1,41 +1,19 @@
-use std::error::Error;
 use std::fmt::{self, Display};
+use std::io::Write;
 
+pub mod diagnostics;
+mod error;
+pub mod error_codes;
+mod formatter;
 mod scanner;
 mod token;
 
-use crate::scanner::scan;
-use crate::token::{Token, TokenContext};
-
-#[derive(Debug)]
-pub struct ScanError {
-    message: String,
-    line: usize,
-    position: usize,
-}
-
-#[derive(Debug)]
-pub enum FellowError {
-    CannotReadFile,
-    InterpreterError,
-    ScanError(ScanError),
-}
-
-impl Error for FellowError {}
-
-impl fmt::Display for FellowError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match &self {
-            Self::CannotReadFile => write!(f, "Cannot read file"),
-            Self::InterpreterError => write!(f, "Fellow interpreter error"),
-            Self::ScanError(err) => write!(
-                f,
-                "Fellow scanner error occured at line {}:{}\n\t{}",
-                err.line, err.position, err.message
-            ),
-        }
-    }
-}
+pub use crate::error::{
+    FellowError, IoError, IoOperation, RuntimeError, RuntimeErrorKind, ScanError, ScanErrorKind,
+};
+pub use crate::formatter::format;
+pub use crate::scanner::scan;
+pub use crate::token::{KEYWORDS, Token, TokenContext};
 
 pub enum FellowValue {
     Int(i64),
@@ -60,16 +38,20 @@ impl Display for FellowValue {
 // Pipeline for our interpreter
 pub fn interpret(source_code: &str) -> Result<FellowValue, FellowError> {
     let tokens = scan(source_code)?;
-    match tokens
-        .into_iter()
-        .filter(|t| !t.token.is_whitespace())
-        .next_back()
-    {
+    match tokens.into_iter().rfind(|t| !t.token.is_whitespace()) {
         Some(v) => Ok(parse_token(v)),
-        None => Err(FellowError::InterpreterError),
+        None => Err(RuntimeError::new(RuntimeErrorKind::NothingToEvaluate, None).into()),
     }
 }
 
+// Runs a script, writing anything that it prints to `out`. There is no print statement yet,
+// so for now the only output is the value of the last token.
+pub fn run(source_code: &str, out: &mut impl Write) -> Result<(), FellowError> {
+    let value = interpret(source_code)?;
+    writeln!(out, "{}", value)
+        .map_err(|err| IoError::new(IoOperation::WriteOutput, None, err).into())
+}
+
 fn parse_token(token_context: TokenContext) -> FellowValue {
     match token_context.token {
         Token::True => FellowValue::Boolean(true),
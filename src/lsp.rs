@@ -0,0 +1,462 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::process::ExitCode;
+
+use serde_json::{Value, json};
+use unicode_segmentation::UnicodeSegmentation;
+
+use fellow::diagnostics::{Diagnostic, Severity};
+use fellow::{Token, scan};
+
+// JSON-RPC error codes from the LSP specification
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_NOT_INITIALIZED: i64 = -32002;
+
+// The semantic token types the server reports, in the order given in the legend. The numbers
+// in semantic token data are indexes into this list.
+const TOKEN_TYPES: &[&str] = &[
+    "keyword", "variable", "string", "number", "operator", "comment",
+];
+
+// A language server that speaks LSP over stdin and stdout. Documents are synced in full on
+// every change, and then re-scanned to publish diagnostics.
+//
+// Only what can be worked out from the token stream is supported: diagnostics, semantic tokens
+// and formatting. Hover, go-to-definition, references and document symbols need a parser and a
+// resolver, so they aren't advertised to the client yet.
+pub struct Server<R, W> {
+    input: R,
+    output: W,
+    // The text of every open document, keyed by URI
+    documents: HashMap<String, String>,
+    initialized: bool,
+    shutdown: bool,
+}
+
+impl<R: BufRead, W: Write> Server<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Self {
+            input,
+            output,
+            documents: HashMap::new(),
+            initialized: false,
+            shutdown: false,
+        }
+    }
+
+    // Handles messages until the client sends `exit`. The exit code follows the specification:
+    // 0 if the client asked for a shutdown first, 1 otherwise.
+    pub fn serve(&mut self) -> ExitCode {
+        loop {
+            let body = match read_message(&mut self.input) {
+                Ok(Some(body)) => body,
+                // The client went away without saying goodbye
+                Ok(None) => return ExitCode::FAILURE,
+                Err(err) => {
+                    eprintln!("Failed to read a message from the client: {}", err);
+                    return ExitCode::FAILURE;
+                }
+            };
+            // A message that isn't JSON gets an error, but the next one might be fine
+            let message: Value = match serde_json::from_slice(&body) {
+                Ok(message) => message,
+                Err(err) => {
+                    let response = json!({
+                        "jsonrpc": "2.0",
+                        "id": null,
+                        "error": { "code": PARSE_ERROR, "message": err.to_string() },
+                    });
+                    if let Err(err) = write_message(&mut self.output, &response) {
+                        eprintln!("Failed to write a message to the client: {}", err);
+                        return ExitCode::FAILURE;
+                    }
+                    continue;
+                }
+            };
+            if message["method"] == "exit" {
+                return if self.shutdown {
+                    ExitCode::SUCCESS
+                } else {
+                    ExitCode::FAILURE
+                };
+            }
+            if let Err(err) = self.handle(message) {
+                eprintln!("Failed to write a message to the client: {}", err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    fn handle(&mut self, message: Value) -> io::Result<()> {
+        // Messages without a method are responses to requests from the server. The server
+        // doesn't send any requests, so there's nothing to do with them.
+        let Some(method) = message["method"].as_str() else {
+            return Ok(());
+        };
+        let params = &message["params"];
+        // Messages without an id are notifications, which don't get a response
+        let Some(id) = message.get("id") else {
+            return self.notification(method, params);
+        };
+
+        let result = if !self.initialized && method != "initialize" {
+            Err((
+                SERVER_NOT_INITIALIZED,
+                "initialize has not been called".to_string(),
+            ))
+        } else {
+            self.request(method, params)
+        };
+        let response = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": code, "message": message },
+            }),
+        };
+        write_message(&mut self.output, &response)
+    }
+
+    fn request(&mut self, method: &str, params: &Value) -> Result<Value, (i64, String)> {
+        match method {
+            "initialize" => {
+                self.initialized = true;
+                Ok(json!({
+                    "capabilities": {
+                        // Full text on every change
+                        "textDocumentSync": 1,
+                        "semanticTokensProvider": {
+                            "legend": { "tokenTypes": TOKEN_TYPES, "tokenModifiers": [] },
+                            "full": true,
+                        },
+                        "documentFormattingProvider": true,
+                    },
+                    "serverInfo": { "name": "fellow", "version": env!("CARGO_PKG_VERSION") },
+                }))
+            }
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            }
+            "textDocument/semanticTokens/full" => {
+                let text = self.document(params)?;
+                Ok(json!({ "data": semantic_tokens(text) }))
+            }
+            "textDocument/formatting" => {
+                let text = self.document(params)?;
+                // A script that can't be formatted already has a diagnostic explaining why, so
+                // there's nothing to change
+                match fellow::format(text) {
+                    Ok(formatted) if formatted != *text => {
+                        let end = Positions::new(text).end();
+                        Ok(json!([{
+                            "range": { "start": position(0, 0), "end": end },
+                            "newText": formatted,
+                        }]))
+                    }
+                    _ => Ok(json!([])),
+                }
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("{} is not supported", method))),
+        }
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) -> io::Result<()> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.to_string(), text.to_string());
+                self.publish_diagnostics(uri)
+            }
+            "textDocument/didChange" => {
+                // With full sync the last change holds the whole document
+                let changes = params["contentChanges"].as_array();
+                if let Some(text) = changes
+                    .and_then(|c| c.last())
+                    .and_then(|c| c["text"].as_str())
+                {
+                    self.documents.insert(uri.to_string(), text.to_string());
+                }
+                self.publish_diagnostics(uri)
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                // Clear the diagnostics of a closed document
+                let params = json!({ "uri": uri, "diagnostics": [] });
+                self.notify("textDocument/publishDiagnostics", params)
+            }
+            // Everything else, like `initialized`, can be ignored
+            _ => Ok(()),
+        }
+    }
+
+    fn document(&self, params: &Value) -> Result<&String, (i64, String)> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        self.documents
+            .get(uri)
+            .ok_or_else(|| (INVALID_PARAMS, format!("{} is not open", uri)))
+    }
+
+    fn publish_diagnostics(&mut self, uri: &str) -> io::Result<()> {
+        let text = self
+            .documents
+            .get(uri)
+            .map(String::as_str)
+            .unwrap_or_default();
+        let diagnostics: Vec<Value> = match scan(text) {
+            Ok(_) => Vec::new(),
+            Err(err) => vec![lsp_diagnostic(&Diagnostic::from_error(&err), text)],
+        };
+        let params = json!({ "uri": uri, "diagnostics": diagnostics });
+        self.notify("textDocument/publishDiagnostics", params)
+    }
+
+    fn notify(&mut self, method: &str, params: Value) -> io::Result<()> {
+        let notification = json!({ "jsonrpc": "2.0", "method": method, "params": params });
+        write_message(&mut self.output, &notification)
+    }
+}
+
+// Reads the body of one message, or None at the end of input. Each message is a JSON body
+// preceded by HTTP-like headers, of which only Content-Length matters.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Vec<u8>>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("Content-Length")
+        {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let Some(length) = length else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "message has no Content-Length header",
+        ));
+    };
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+fn lsp_diagnostic(diagnostic: &Diagnostic, text: &str) -> Value {
+    let positions = Positions::new(text);
+    let range = match &diagnostic.primary {
+        Some(label) => json!({
+            "start": positions.of(label.span.start),
+            "end": positions.of(label.span.end),
+        }),
+        None => json!({ "start": position(0, 0), "end": position(0, 0) }),
+    };
+    // Editors show the message on its own, so help goes underneath it like in the terminal
+    let mut message = diagnostic.message.clone();
+    for help in &diagnostic.help {
+        message.push_str("\nhelp: ");
+        message.push_str(help);
+    }
+    let severity = match diagnostic.severity {
+        Severity::Error => 1,
+        Severity::Warning => 2,
+    };
+    json!({
+        "range": range,
+        "severity": severity,
+        "code": diagnostic.code,
+        "source": "fellow",
+        "message": message,
+    })
+}
+
+// Semantic tokens are encoded as groups of five numbers: the line relative to the previous
+// token, the start column (relative to the previous token if it's on the same line), the
+// length, the token type and its modifiers. Tokens can't span lines, so a string over several
+// lines becomes one token per line.
+fn semantic_tokens(text: &str) -> Vec<u32> {
+    // Scripts that don't scan have a diagnostic instead
+    let Ok(tokens) = scan(text) else {
+        return Vec::new();
+    };
+    let graphemes: Vec<&str> = text.graphemes(true).collect();
+    let positions = Positions::new(text);
+
+    let mut data = Vec::new();
+    let (mut previous_line, mut previous_column) = (0, 0);
+    for tc in &tokens {
+        let Some(token_type) = semantic_token_type(&tc.token) else {
+            continue;
+        };
+        let mut segment_start = tc.start();
+        for offset in tc.start()..=tc.end() {
+            let at_newline = graphemes.get(offset).is_some_and(|g| is_newline(g));
+            if offset < tc.end() && !at_newline {
+                continue;
+            }
+            let (line, column) = positions.line_and_column(segment_start);
+            let (_, end_column) = positions.line_and_column(offset);
+            if end_column > column {
+                let delta_column = if line == previous_line {
+                    column - previous_column
+                } else {
+                    column
+                };
+                data.extend([line - previous_line, delta_column, end_column - column]);
+                data.extend([token_type, 0]);
+                (previous_line, previous_column) = (line, column);
+            }
+            segment_start = offset + 1;
+        }
+    }
+    data
+}
+
+fn semantic_token_type(token: &Token) -> Option<u32> {
+    let name = match token {
+        Token::And
+        | Token::Class
+        | Token::Else
+        | Token::False
+        | Token::Fun
+        | Token::For
+        | Token::If
+        | Token::Nil
+        | Token::Or
+        | Token::Print
+        | Token::Return
+        | Token::Super
+        | Token::This
+        | Token::True
+        | Token::Var
+        | Token::While
+        | Token::Boolean(_) => "keyword",
+        Token::Identifier(_) => "variable",
+        Token::String(_) => "string",
+        Token::Integer(_) | Token::Float(_) => "number",
+        Token::Minus
+        | Token::Plus
+        | Token::Slash
+        | Token::ForwardSlash
+        | Token::Star
+        | Token::Bang
+        | Token::BangEqual
+        | Token::Equal
+        | Token::EqualEqual
+        | Token::Greater
+        | Token::GreaterEqual
+        | Token::Less
+        | Token::LessEqual => "operator",
+        Token::Comment(_) => "comment",
+        // Punctuation and whitespace aren't highlighted
+        _ => return None,
+    };
+    TOKEN_TYPES
+        .iter()
+        .position(|t| *t == name)
+        .map(|i| i as u32)
+}
+
+fn is_newline(grapheme: &str) -> bool {
+    grapheme == "\n" || grapheme == "\r\n"
+}
+
+fn position(line: u32, character: u32) -> Value {
+    json!({ "line": line, "character": character })
+}
+
+// Converts the grapheme offsets used by the scanner into LSP positions. LSP counts columns in
+// UTF-16 code units, so a grapheme like an emoji can take up several of them.
+struct Positions {
+    // The line and column of every grapheme, plus one more for the end of the text
+    positions: Vec<(u32, u32)>,
+}
+
+impl Positions {
+    fn new(text: &str) -> Self {
+        let mut positions = Vec::new();
+        let (mut line, mut column) = (0, 0);
+        for grapheme in text.graphemes(true) {
+            positions.push((line, column));
+            if is_newline(grapheme) {
+                (line, column) = (line + 1, 0);
+            } else {
+                column += grapheme.encode_utf16().count() as u32;
+            }
+        }
+        positions.push((line, column));
+        Self { positions }
+    }
+
+    fn line_and_column(&self, offset: usize) -> (u32, u32) {
+        let last = self.positions.len() - 1;
+        self.positions[offset.min(last)]
+    }
+
+    fn of(&self, offset: usize) -> Value {
+        let (line, column) = self.line_and_column(offset);
+        position(line, column)
+    }
+
+    fn end(&self) -> Value {
+        self.of(self.positions.len() - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_columns_in_utf16() {
+        let positions = Positions::new("\"👋\"\r\n1");
+        assert_eq!(positions.line_and_column(2), (0, 3));
+        assert_eq!(positions.line_and_column(4), (1, 0));
+        assert_eq!(positions.end(), position(1, 1));
+    }
+
+    #[test]
+    fn encodes_semantic_tokens_relative_to_each_other() {
+        // 1 + "a
+        // b" // c
+        let data = semantic_tokens("1 + \"a\nb\" // c\n");
+        assert_eq!(
+            data,
+            [
+                0, 0, 1, 3, 0, // 1
+                0, 2, 1, 4, 0, // +
+                0, 2, 2, 2, 0, // "a
+                1, 0, 2, 2, 0, // b"
+                0, 3, 4, 5, 0, // // c
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_messages_framed_by_headers() {
+        let mut output = Vec::new();
+        write_message(&mut output, &json!({ "id": 1 })).unwrap();
+        assert_eq!(output, b"Content-Length: 8\r\n\r\n{\"id\":1}");
+        let mut input = &output[..];
+        assert_eq!(
+            read_message(&mut input).unwrap(),
+            Some(b"{\"id\":1}".to_vec())
+        );
+        assert_eq!(read_message(&mut input).unwrap(), None);
+    }
+}
@@ -1,47 +1,330 @@
-use std::io::stdin;
-use std::path::PathBuf;
+use std::env;
+use std::io::{IsTerminal, Read, stderr, stdin, stdout};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
+use unicode_width::UnicodeWidthStr;
 
-use fellow::interpret;
+use fellow::diagnostics::Diagnostic;
+use fellow::error_codes;
+use fellow::{FellowError, IoError, IoOperation, Token, TokenContext, run, scan};
+
+mod lsp;
+mod repl;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    #[arg(value_parser, value_name = "SCRIPT")]
-    path: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// How errors are written to stderr
+    #[arg(long, global = true, value_enum, default_value_t = ErrorFormat::Human)]
+    error_format: ErrorFormat,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ErrorFormat {
+    /// Source snippets with underlines, colored when stderr is a terminal
+    Human,
+    /// One JSON object per diagnostic, each on its own line
+    Json,
+}
+
+// Every command that takes a script reads it from stdin when the path is `-`
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run a script
+    Run {
+        #[arg(value_parser, value_name = "SCRIPT")]
+        path: PathBuf,
+    },
+    /// Print the tokens that the scanner finds in a script. Lines and columns count from 1,
+    /// start and end are grapheme offsets into the source counting from 0.
+    Tokens {
+        #[arg(value_parser, value_name = "SCRIPT")]
+        path: PathBuf,
+        /// Leave out spaces, tabs and newlines
+        #[arg(long)]
+        hide_whitespace: bool,
+        /// Leave out comments
+        #[arg(long)]
+        hide_comments: bool,
+        /// Print one JSON object per token instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print the syntax tree of a script
+    Ast {
+        #[arg(value_parser, value_name = "SCRIPT")]
+        path: PathBuf,
+    },
+    /// Check a script for errors without running it
+    Check {
+        #[arg(value_parser, value_name = "SCRIPT")]
+        path: PathBuf,
+    },
+    /// Run the tests in a script
+    Test {
+        #[arg(value_parser, value_name = "SCRIPT")]
+        path: PathBuf,
+    },
+    /// Start an interactive session. This is the default when no command is given.
+    Repl,
+    /// Format a script in place, or print it formatted when reading from stdin
+    Fmt {
+        #[arg(value_parser, value_name = "SCRIPT")]
+        path: PathBuf,
+        /// Don't write anything, just exit with 1 if the script isn't formatted
+        #[arg(long)]
+        check: bool,
+    },
+    /// Start a language server that speaks LSP over stdin and stdout
+    Lsp,
+    /// Explain an error code in detail, like F0101
+    Explain {
+        #[arg(value_name = "CODE")]
+        code: String,
+    },
 }
 
-fn main() {
+// Exit code for commands that exist in the CLI but aren't backed by the interpreter yet, so that
+// tooling can tell them apart from a script that failed.
+const EXIT_UNSUPPORTED: u8 = 3;
+
+fn main() -> ExitCode {
     let args = Args::parse();
 
-    println!("{:?}", args.path);
+    let format = args.error_format;
+    match args.command {
+        Some(Command::Run { path }) => with_source(&path, format, run_script),
+        Some(Command::Tokens {
+            path,
+            hide_whitespace,
+            hide_comments,
+            json,
+        }) => with_source(&path, format, |source| {
+            print_tokens(source, hide_whitespace, hide_comments, json)
+        }),
+        Some(Command::Check { path }) => with_source(&path, format, check_script),
+        Some(Command::Ast { .. }) => unsupported("ast", "Fellow doesn't have a parser yet"),
+        Some(Command::Test { .. }) => unsupported("test", "Fellow doesn't have test blocks yet"),
+        Some(Command::Fmt { path, check }) => format_script(&path, check, format),
+        Some(Command::Explain { code }) => explain_code(&code),
+        Some(Command::Lsp) => lsp::Server::new(stdin().lock(), stdout().lock()).serve(),
+        Some(Command::Repl) | None => {
+            repl::Session::new(format).run();
+            ExitCode::SUCCESS
+        }
+    }
+}
+
+fn unsupported(command: &str, reason: &str) -> ExitCode {
+    eprintln!("fellow {} is not supported yet: {}", command, reason);
+    ExitCode::from(EXIT_UNSUPPORTED)
+}
+
+// A script and the name to use for it in diagnostics
+struct Source {
+    name: String,
+    contents: String,
+}
+
+fn read_source(path: &Path) -> Result<Source, FellowError> {
+    if path == Path::new("-") {
+        let mut contents = String::new();
+        stdin()
+            .read_to_string(&mut contents)
+            .map_err(|err| IoError::new(IoOperation::ReadScript, None, err))?;
+        Ok(Source {
+            name: "<stdin>".to_string(),
+            contents,
+        })
+    } else {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|err| IoError::new(IoOperation::ReadScript, Some(path.to_path_buf()), err))?;
+        Ok(Source {
+            name: path.display().to_string(),
+            contents,
+        })
+    }
+}
 
-    match args.path {
-        Some(path) => run_script(&path),
-        None => run_interactive(),
+// Reads the script at `path` and runs a command on it, reporting any errors
+fn with_source(
+    path: &Path,
+    format: ErrorFormat,
+    command: impl FnOnce(&Source) -> Result<(), FellowError>,
+) -> ExitCode {
+    let source = match read_source(path) {
+        Ok(source) => source,
+        Err(err) => {
+            let source = Source {
+                name: path.display().to_string(),
+                contents: String::new(),
+            };
+            report(&source, &err, format);
+            return ExitCode::FAILURE;
+        }
+    };
+    match command(&source) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            report(&source, &err, format);
+            ExitCode::FAILURE
+        }
     }
 }
 
-fn run_script(path: &PathBuf) {
-    match std::fs::read_to_string(path) {
-        Ok(contents) => match interpret(&contents) {
-            Ok(value) => println!("{}", value),
-            Err(err) => eprintln!("Error {:?}", err),
-        },
-        Err(err) => eprintln!("Failed to read source code from {:?}", err),
+fn report(source: &Source, err: &FellowError, format: ErrorFormat) {
+    let diagnostic = Diagnostic::from_error(err);
+    if let ErrorFormat::Json = format {
+        eprintln!("{}", diagnostic.to_json(&source.name, &source.contents));
+        return;
+    }
+
+    // Respect https://no-color.org as well as checking for a terminal
+    let color = stderr().is_terminal() && env::var_os("NO_COLOR").is_none();
+    eprint!(
+        "{}",
+        diagnostic.render(&source.name, &source.contents, color)
+    );
+    if error_codes::explain(diagnostic.code).is_some() {
+        eprintln!(
+            "For more information about this error, try `fellow explain {}`.",
+            diagnostic.code
+        );
     }
 }
 
-fn run_interactive() {
-    loop {
-        let mut buffer = String::new();
-        match stdin().read_line(&mut buffer) {
-            Ok(_size) => match interpret(&buffer) {
-                Ok(value) => println!("{}", value),
-                Err(err) => eprintln!("Error {:?}", err),
-            },
-            Err(err) => eprintln!("Failed to read line {:?}", err),
+fn explain_code(code: &str) -> ExitCode {
+    match error_codes::explain(code) {
+        Some(explanation) => {
+            print!("{}", explanation);
+            ExitCode::SUCCESS
         }
+        None => {
+            let known: Vec<&str> = error_codes::codes().collect();
+            eprintln!(
+                "{} is not a Fellow error code. Known codes are: {}",
+                code,
+                known.join(", ")
+            );
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run_script(source: &Source) -> Result<(), FellowError> {
+    run(&source.contents, &mut stdout())
+}
+
+fn print_tokens(
+    source: &Source,
+    hide_whitespace: bool,
+    hide_comments: bool,
+    json: bool,
+) -> Result<(), FellowError> {
+    let tokens: Vec<TokenContext> = scan(&source.contents)?
+        .into_iter()
+        .filter(|tc| match &tc.token {
+            Token::Comment(_) => !hide_comments,
+            token if token.is_trivia() => !hide_whitespace,
+            _ => true,
+        })
+        .collect();
+
+    if json {
+        for tc in &tokens {
+            let value = serde_json::json!({
+                "kind": tc.token.name(),
+                "lexeme": tc.lexeme(),
+                "line": tc.line() + 1,
+                "column": tc.column() + 1,
+                "start": tc.start(),
+                "end": tc.end(),
+            });
+            println!("{}", value);
+        }
+    } else {
+        print_token_table(&tokens);
     }
+    Ok(())
+}
+
+fn print_token_table(tokens: &[TokenContext]) {
+    // Lexemes are escaped so that newlines and tabs don't break up the table
+    let rows: Vec<[String; 6]> = tokens
+        .iter()
+        .map(|tc| {
+            [
+                tc.token.name().to_string(),
+                format!("{:?}", tc.lexeme()),
+                (tc.line() + 1).to_string(),
+                (tc.column() + 1).to_string(),
+                tc.start().to_string(),
+                tc.end().to_string(),
+            ]
+        })
+        .collect();
+    let header = ["KIND", "LEXEME", "LINE", "COL", "START", "END"].map(String::from);
+    // Cells are measured in terminal columns, since a lexeme like "😀" is one char but two
+    // columns wide
+    let mut widths = header.clone().map(|h| h.width());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.width());
+        }
+    }
+    for row in std::iter::once(&header).chain(&rows) {
+        // Text columns are left aligned and numbers are right aligned. `{:<w$}` pads by chars
+        // rather than columns, so the padding is worked out by hand.
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .enumerate()
+            .map(|(i, (cell, width))| {
+                let padding = " ".repeat(width - cell.width());
+                if i < 2 {
+                    format!("{}{}", cell, padding)
+                } else {
+                    format!("{}{}", padding, cell)
+                }
+            })
+            .collect();
+        println!("{}", cells.join("  "));
+    }
+}
+
+// Formats a script in place. Scripts read from stdin are printed instead, so that editors can
+// pipe a buffer through `fellow fmt -`.
+fn format_script(path: &Path, check: bool, format: ErrorFormat) -> ExitCode {
+    let mut unformatted = false;
+    let status = with_source(path, format, |source| {
+        let formatted = fellow::format(&source.contents)?;
+        if check {
+            if formatted != source.contents {
+                eprintln!("would reformat {}", source.name);
+                unformatted = true;
+            }
+        } else if path == Path::new("-") {
+            print!("{}", formatted);
+        } else if formatted != source.contents {
+            std::fs::write(path, formatted).map_err(|err| {
+                IoError::new(IoOperation::WriteScript, Some(path.to_path_buf()), err)
+            })?;
+        }
+        Ok(())
+    });
+    if unformatted {
+        ExitCode::FAILURE
+    } else {
+        status
+    }
+}
+
+// Scanning is the only static analysis that Fellow has so far
+fn check_script(source: &Source) -> Result<(), FellowError> {
+    scan(&source.contents)?;
+    Ok(())
 }
@@ -0,0 +1,413 @@
+use std::env;
+use std::fs;
+use std::io::{BufRead, IsTerminal, StdinLock, stdin};
+use std::path::PathBuf;
+use std::time::Instant;
+
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+
+use fellow::{FellowError, IoError, IoOperation, KEYWORDS, ScanErrorKind, Token, interpret, scan};
+
+use crate::{ErrorFormat, Source, print_token_table, report};
+
+const PROMPT: &str = "> ";
+// Shown while an entry is spread over several lines
+const CONTINUATION_PROMPT: &str = ". ";
+
+const COMMANDS: &[&str] = &[
+    ":tokens", ":ast", ":type", ":env", ":time", ":load", ":save", ":reset", ":help", ":quit",
+];
+
+const HELP: &str = "\
+Enter Fellow code to run it, or one of these commands:
+  :tokens <code>   print the tokens in some code
+  :ast <code>      print the syntax tree of some code
+  :type <code>     print the type of an expression
+  :env             list the bindings in the session
+  :time <code>     run some code and print how long it took
+  :load <file>     run a script in the session
+  :save <file>     write every entry that ran successfully to a script
+  :reset           forget everything entered so far
+  :help            show this message
+  :quit            leave the REPL (so does Ctrl-D)";
+
+// An interactive session. Lines are collected until they make up a complete entry, so that
+// strings and braces can be continued over several lines, and then the entry is interpreted.
+//
+// Entries are still interpreted independently. Once the interpreter has an environment, the
+// session is where it should live so that bindings survive from one entry to the next.
+pub struct Session {
+    format: ErrorFormat,
+    // The lines of the current entry that have been read so far
+    pending: String,
+    // Every entry that ran without an error, so that :save writes a script that works
+    entries: Vec<String>,
+    quit: bool,
+}
+
+impl Session {
+    pub fn new(format: ErrorFormat) -> Self {
+        Self {
+            format,
+            pending: String::new(),
+            entries: Vec::new(),
+            quit: false,
+        }
+    }
+
+    // Reads entries until the end of input or :quit
+    pub fn run(&mut self) {
+        let mut input = LineReader::new();
+        while !self.quit {
+            let prompt = if self.pending.is_empty() {
+                PROMPT
+            } else {
+                CONTINUATION_PROMPT
+            };
+            match input.read_line(prompt) {
+                Ok(ReadLine::Line(line)) => self.feed(&line),
+                // Ctrl-C throws away the entry being typed, like in a shell
+                Ok(ReadLine::Interrupted) => self.pending.clear(),
+                Ok(ReadLine::End) => {
+                    self.finish();
+                    break;
+                }
+                Err(err) => {
+                    eprintln!("Failed to read line: {}", err);
+                    break;
+                }
+            }
+        }
+        input.save_history();
+    }
+
+    fn feed(&mut self, line: &str) {
+        // Commands can only start an entry, since a line starting with `:` could be part of a
+        // longer one.
+        if self.pending.is_empty() && line.trim_start().starts_with(':') {
+            self.command(line.trim());
+            return;
+        }
+        self.pending.push_str(line);
+        if !is_incomplete(&self.pending) {
+            self.evaluate();
+        }
+    }
+
+    // At the end of input, an entry that is still open is reported rather than silently dropped.
+    // An unterminated string gets its scanner error from being interpreted, but the interpreter
+    // doesn't look at brackets, so those are checked here.
+    fn finish(&mut self) {
+        if has_unclosed_brackets(&self.pending) {
+            eprintln!(
+                "The input ended before the last entry was finished: a bracket is never closed"
+            );
+            self.pending.clear();
+            return;
+        }
+        self.evaluate();
+    }
+
+    fn evaluate(&mut self) {
+        let entry = std::mem::take(&mut self.pending);
+        // Pressing enter on an empty line shouldn't be an error
+        if entry.trim().is_empty() {
+            return;
+        }
+        self.interpret("<repl>", entry);
+    }
+
+    // Interprets an entry and prints its value, remembering it for :save if it worked
+    fn interpret(&mut self, name: &str, mut entry: String) {
+        match interpret(&entry) {
+            Ok(value) => {
+                println!("{}", value);
+                // A script loaded with :load might not end with a newline, and it shouldn't run
+                // into the next entry when the session is saved
+                if !entry.ends_with('\n') {
+                    entry.push('\n');
+                }
+                self.entries.push(entry);
+            }
+            Err(err) => self.report(name, entry, &err),
+        }
+    }
+
+    fn report(&self, name: &str, contents: String, err: &FellowError) {
+        let source = Source {
+            name: name.to_string(),
+            contents,
+        };
+        report(&source, err, self.format);
+    }
+
+    fn command(&mut self, line: &str) {
+        let (name, argument) = match line.split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, argument.trim()),
+            None => (line, ""),
+        };
+        let needs_argument = matches!(name, ":tokens" | ":time" | ":load" | ":save");
+        if needs_argument && argument.is_empty() {
+            eprintln!(
+                "{} needs an argument. Type :help to see how to use it.",
+                name
+            );
+            return;
+        }
+
+        match name {
+            ":tokens" => match scan(argument) {
+                Ok(tokens) => print_token_table(&tokens),
+                Err(err) => self.report("<repl>", argument.to_string(), &err),
+            },
+            ":ast" => eprintln!(":ast is not supported yet: Fellow doesn't have a parser yet"),
+            ":type" => eprintln!(":type is not supported yet: Fellow doesn't have types yet"),
+            ":env" => eprintln!(":env is not supported yet: Fellow doesn't have bindings yet"),
+            ":time" => {
+                let start = Instant::now();
+                let result = interpret(argument);
+                let elapsed = start.elapsed();
+                match result {
+                    Ok(value) => println!("{}", value),
+                    Err(err) => self.report("<repl>", argument.to_string(), &err),
+                }
+                println!("took {:?}", elapsed);
+            }
+            ":load" => match fs::read_to_string(argument) {
+                Ok(contents) => self.interpret(argument, contents),
+                Err(err) => {
+                    let path = Some(PathBuf::from(argument));
+                    let err = IoError::new(IoOperation::ReadScript, path, err).into();
+                    self.report(argument, String::new(), &err);
+                }
+            },
+            ":save" => match fs::write(argument, self.entries.concat()) {
+                Ok(()) => println!("Saved {} entries to {}", self.entries.len(), argument),
+                Err(err) => {
+                    let path = Some(PathBuf::from(argument));
+                    let err = IoError::new(IoOperation::WriteScript, path, err).into();
+                    self.report(argument, String::new(), &err);
+                }
+            },
+            ":reset" => {
+                self.entries.clear();
+                println!("Session reset");
+            }
+            ":help" => println!("{}", HELP),
+            ":quit" | ":q" => self.quit = true,
+            _ => eprintln!("Unknown command {}. Type :help to see the commands.", name),
+        }
+    }
+}
+
+enum ReadLine {
+    // A line of input, including its newline
+    Line(String),
+    Interrupted,
+    End,
+}
+
+// Where the REPL gets its input from. A person at a terminal gets a line editor with history and
+// completion. Anything else, like a script piped into `fellow repl`, is read line by line without
+// prompts, so that the output is nothing but results.
+enum LineReader {
+    Editor(Box<Editor<FellowHelper, DefaultHistory>>),
+    Plain(StdinLock<'static>),
+}
+
+impl LineReader {
+    fn new() -> Self {
+        if stdin().is_terminal()
+            && let Ok(mut editor) = Editor::new()
+        {
+            editor.set_helper(Some(FellowHelper));
+            if let Some(path) = history_path() {
+                // There's no history the first time the REPL runs
+                let _ = editor.load_history(&path);
+            }
+            return Self::Editor(Box::new(editor));
+        }
+        Self::Plain(stdin().lock())
+    }
+
+    fn read_line(&mut self, prompt: &str) -> Result<ReadLine, ReadlineError> {
+        match self {
+            Self::Editor(editor) => match editor.readline(prompt) {
+                Ok(line) => {
+                    if !line.trim().is_empty() {
+                        editor.add_history_entry(line.as_str())?;
+                    }
+                    Ok(ReadLine::Line(line + "\n"))
+                }
+                Err(ReadlineError::Interrupted) => Ok(ReadLine::Interrupted),
+                Err(ReadlineError::Eof) => Ok(ReadLine::End),
+                Err(err) => Err(err),
+            },
+            Self::Plain(input) => {
+                let mut line = String::new();
+                match input.read_line(&mut line)? {
+                    0 => Ok(ReadLine::End),
+                    _ => Ok(ReadLine::Line(line)),
+                }
+            }
+        }
+    }
+
+    fn save_history(&mut self) {
+        if let (Self::Editor(editor), Some(path)) = (self, history_path())
+            && let Err(err) = editor.save_history(&path)
+        {
+            eprintln!("Failed to save history to {}: {}", path.display(), err);
+        }
+    }
+}
+
+// History is kept in $FELLOW_HISTORY, or ~/.fellow_history by default
+fn history_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("FELLOW_HISTORY") {
+        return Some(PathBuf::from(path));
+    }
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(".fellow_history"))
+}
+
+struct FellowHelper;
+
+impl Completer for FellowHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(completions(line, pos))
+    }
+}
+
+impl Hinter for FellowHelper {
+    type Hint = String;
+}
+
+impl Highlighter for FellowHelper {}
+
+impl Validator for FellowHelper {}
+
+impl Helper for FellowHelper {}
+
+// Completes the word that ends at `pos`, returning where the word starts and what it could be.
+// REPL commands are completed at the start of a line, keywords everywhere else. There are no
+// builtins or bindings to complete yet.
+fn completions(line: &str, pos: usize) -> (usize, Vec<String>) {
+    let before = &line[..pos];
+    let start = before
+        .rfind(|c: char| !(c.is_alphanumeric() || c == '_' || c == ':'))
+        .map(|i| i + before[i..].chars().next().map_or(1, char::len_utf8))
+        .unwrap_or(0);
+    let word = &before[start..];
+    if word.is_empty() {
+        return (pos, Vec::new());
+    }
+
+    let candidates: &[&str] = if word.starts_with(':') && before[..start].trim().is_empty() {
+        COMMANDS
+    } else {
+        KEYWORDS
+    };
+    let matches = candidates
+        .iter()
+        .filter(|candidate| candidate.starts_with(word))
+        .map(|candidate| candidate.to_string())
+        .collect();
+    (start, matches)
+}
+
+// An entry is incomplete when it ends inside a string or has more opening brackets than closing
+// ones. Any other error means the entry is complete but wrong, so it's reported straight away.
+fn is_incomplete(source: &str) -> bool {
+    match scan(source) {
+        Ok(_) => has_unclosed_brackets(source),
+        Err(FellowError::Scan(err)) => err.kind() == &ScanErrorKind::UnterminatedString,
+        Err(_) => false,
+    }
+}
+
+fn has_unclosed_brackets(source: &str) -> bool {
+    let Ok(tokens) = scan(source) else {
+        return false;
+    };
+    let mut depth: isize = 0;
+    for token_context in tokens {
+        match token_context.token {
+            Token::LeftParen | Token::LeftBrace => depth += 1,
+            Token::RightParen | Token::RightBrace => depth -= 1,
+            _ => {}
+        }
+    }
+    depth > 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn waits_for_closing_quotes_and_brackets() {
+        assert!(is_incomplete("\"hello\n"));
+        assert!(is_incomplete("{\n"));
+        assert!(is_incomplete("({}\n"));
+        assert!(!is_incomplete("\"hello\n\"\n"));
+        assert!(!is_incomplete("{ 1 }\n"));
+    }
+
+    #[test]
+    fn completes_keywords_and_commands() {
+        assert_eq!(completions("wh", 2), (0, vec!["while".to_string()]));
+        assert_eq!(
+            completions("1 f", 3),
+            (2, ["false", "fun", "for"].map(String::from).to_vec())
+        );
+        assert_eq!(completions(":lo", 3), (0, vec![":load".to_string()]));
+        // Commands only make sense at the start of a line
+        assert_eq!(completions("1 :lo", 5), (2, Vec::new()));
+        assert_eq!(completions("1 ", 2), (2, Vec::new()));
+    }
+
+    #[test]
+    fn saves_each_entry_on_its_own_line() {
+        let mut session = Session::new(ErrorFormat::Human);
+        session.interpret("a.fellow", "42".to_string());
+        session.feed("1\n");
+        assert_eq!(session.entries.concat(), "42\n1\n");
+    }
+
+    #[test]
+    fn reports_unfinished_entries_at_the_end_of_input() {
+        let mut session = Session::new(ErrorFormat::Human);
+        session.feed("{\n");
+        session.finish();
+        assert!(session.pending.is_empty());
+        // The dangling brace didn't run, so there's nothing to save
+        assert!(session.entries.is_empty());
+
+        session.feed("1\n");
+        session.feed("\"open\n");
+        session.finish();
+        assert_eq!(session.entries, ["1\n"]);
+    }
+
+    #[test]
+    fn reports_other_errors_straight_away() {
+        assert!(!is_incomplete("{ ?\n"));
+        // Too many closing brackets can't be fixed by reading more lines
+        assert!(!is_incomplete("}\n"));
+    }
+}
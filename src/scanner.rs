@@ -1,6 +1,5 @@
-use std::str::pattern::Pattern;
-
-use crate::{FellowError, ScanError, Token, TokenContext};
+use crate::diagnostics::Span;
+use crate::{FellowError, ScanError, ScanErrorKind, Token, TokenContext};
 
 use unicode_segmentation::UnicodeSegmentation;
 
@@ -9,8 +8,13 @@ use unicode_segmentation::UnicodeSegmentation;
 pub struct ScanState<'a> {
     source: Vec<&'a str>,
     lexeme_start: usize,
+    // Where the current lexeme started, as a line and a column within that line
+    lexeme_line: usize,
+    lexeme_column: usize,
     current_grapheme: usize,
     current_line: usize,
+    // The grapheme that the current line starts at, used to calculate columns
+    line_start: usize,
 }
 
 impl<'a> ScanState<'a> {
@@ -22,13 +26,25 @@ impl<'a> ScanState<'a> {
         Self {
             source: source_code.graphemes(true).collect(),
             lexeme_start: 0,
+            lexeme_line: 0,
+            lexeme_column: 0,
             current_grapheme: 0,
             current_line: 0,
+            line_start: 0,
         }
     }
 
     fn mark_lexeme_start(&mut self) {
         self.lexeme_start = self.current_grapheme;
+        self.lexeme_line = self.current_line;
+        self.lexeme_column = self.current_grapheme - self.line_start;
+    }
+
+    // Called after consuming a newline. Tokens can span lines (strings and comments), so this
+    // is separate from the NewLine token.
+    fn new_line(&mut self) {
+        self.current_line += 1;
+        self.line_start = self.current_grapheme;
     }
 
     fn lexeme(&self) -> String {
@@ -135,8 +151,9 @@ impl<'a> ScanState<'a> {
             " " => Ok(self.contextualize(Token::Space)),
             "\r" => Ok(self.contextualize(Token::CarriageReturn)),
             "\t" => Ok(self.contextualize(Token::Tab)),
-            "\n" => {
-                self.current_line += 1;
+            // Windows line endings are a single grapheme
+            "\n" | "\r\n" => {
+                self.new_line();
                 Ok(self.contextualize(Token::NewLine))
             }
             "\"" => self.string(),
@@ -147,7 +164,10 @@ impl<'a> ScanState<'a> {
             // if I should just index into c[0] and pass it in, or try to understand which
             // graphemes bytes count as non XID_start chars.
             "TODO" => self.keyword_or_identifier(),
-            _ => Err(self.error()),
+            c => {
+                let kind = ScanErrorKind::UnexpectedCharacter(c.to_string());
+                Err(self.error(kind, self.lexeme_span()))
+            }
         }
     }
 
@@ -158,7 +178,8 @@ impl<'a> ScanState<'a> {
         TokenContext::new(
             token,
             self.lexeme(),
-            self.current_line,
+            self.lexeme_line,
+            self.lexeme_column,
             self.lexeme_start,
             self.current_grapheme,
         )
@@ -168,17 +189,14 @@ impl<'a> ScanState<'a> {
 
     fn string(&mut self) -> Result<TokenContext, FellowError> {
         while self.peek() != "\"" && !self.is_at_end() {
-            if self.peek() == "\n" {
-                self.current_line += 1;
+            if is_newline(self.next()) {
+                self.new_line();
             }
-            self.next();
         }
         if self.is_at_end() {
-            Err(FellowError::ScanError(ScanError {
-                message: format!("Unterminated string {}", self.lexeme()),
-                line: self.current_line,
-                position: self.current_grapheme,
-            }))
+            // Point at the opening quote, since the rest of the file is part of the lexeme
+            let opening_quote = Span::new(self.lexeme_start, self.lexeme_start + 1);
+            Err(self.error(ScanErrorKind::UnterminatedString, opening_quote))
         } else {
             // Consume the final "
             self.next();
@@ -196,50 +214,59 @@ impl<'a> ScanState<'a> {
         }
         match self.lexeme().parse() {
             Ok(value) => Ok(self.contextualize(Token::Integer(value))),
-            Err(e) => Err(FellowError::ScanError(ScanError {
-                message: e.to_string(),
-                line: self.current_line,
-                position: self.current_grapheme,
-            })),
+            Err(e) => Err(self.error(
+                ScanErrorKind::InvalidInteger(e.to_string()),
+                self.lexeme_span(),
+            )),
         }
     }
 
     fn comment(&mut self) -> Result<TokenContext, FellowError> {
-        while self.peek() != "\n" && !self.is_at_end() {
+        while !is_newline(self.peek()) && !self.is_at_end() {
             self.next();
         }
-        if self.peek() == "\n" {
-            self.current_line += 1;
+        // The +2 is to skip the // characters since we only want the text of the comment.
+        // The newline that follows the comment isn't part of the text, but it is part of the
+        // lexeme. The last line of a file might not have one.
+        let text = self.source[self.lexeme_start + 2..self.current_grapheme].concat();
+        if is_newline(self.peek()) {
             self.next();
+            self.new_line();
         }
-        // The +2 is to skip the // characters since we only want the text of the comment.
-        // The -1 is to cut off the newline that follows the comment
-        Ok(self.contextualize(Token::Comment(
-            self.source[self.lexeme_start + 2..self.current_grapheme - 1].concat(),
-        )))
+        Ok(self.contextualize(Token::Comment(text)))
+    }
+
+    fn lexeme_span(&self) -> Span {
+        Span::new(self.lexeme_start, self.current_grapheme)
     }
 
-    fn error(&self) -> FellowError {
-        FellowError::ScanError(ScanError {
-            message: format!("Failed to scan at {}", self.current_grapheme),
-            line: self.current_line,
-            // TODO: This should be an offset from the start of the line. I'd also like to
-            // calculate the length of the error lexeme, but that might be impossible since we haven't
-            // finished lexing yet.
-            position: self.current_grapheme,
-        })
+    // Errors are reported at the line and column where the lexeme started, with a span covering
+    // whichever part of the lexeme is to blame.
+    fn error(&self, kind: ScanErrorKind, span: Span) -> FellowError {
+        ScanError::new(kind, self.lexeme_line, self.lexeme_column, span).into()
     }
 
-    fn keyword_or_identifier(&self) -> Result<TokenContext, FellowError> {
-        while self.peek() && !self.is_at_end() {
+    fn keyword_or_identifier(&mut self) -> Result<TokenContext, FellowError> {
+        while !self.is_at_end() && is_identifier_grapheme(self.peek()) {
             self.next();
         }
-        Ok(self.contextualize(Token::Identifier(
-            self.source[self.lexeme_start + 1..self.current_grapheme - 1].concat(),
-        )))
+        Ok(self.contextualize(Token::Identifier(self.lexeme())))
     }
 }
 
+// Whether a grapheme can be part of an identifier. The first char is the base character, and
+// anything after it, like a combining accent, belongs to that character.
+pub(crate) fn is_identifier_grapheme(grapheme: &str) -> bool {
+    grapheme
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_alphanumeric() || c == '_')
+}
+
+fn is_newline(grapheme: &str) -> bool {
+    grapheme == "\n" || grapheme == "\r\n"
+}
+
 // Take ownership of the source code and turn it into tokens
 pub fn scan(source_code: &str) -> Result<Vec<TokenContext>, FellowError> {
     let mut state = ScanState::new(source_code);
@@ -251,6 +278,7 @@ pub fn scan(source_code: &str) -> Result<Vec<TokenContext>, FellowError> {
         tokens.push(token);
     }
 
+    state.mark_lexeme_start();
     tokens.push(state.contextualize(Token::EndOfFile));
     Ok(tokens)
 }
@@ -343,6 +371,43 @@ There" "#;
         )
     }
 
+    #[test]
+    fn records_where_each_token_starts() {
+        let source = "// Comment\n \"Hello\nThere\" 42";
+        let positions: Vec<(usize, usize, usize, usize)> = scan(source)
+            .unwrap()
+            .iter()
+            .map(|tc| (tc.line(), tc.column(), tc.start(), tc.end()))
+            .collect();
+        assert_eq!(
+            positions,
+            vec![
+                (0, 0, 0, 11),
+                (1, 0, 11, 12),
+                (1, 1, 12, 25),
+                (2, 6, 25, 26),
+                (2, 7, 26, 28),
+                (2, 9, 28, 28),
+            ]
+        )
+    }
+
+    #[test]
+    fn scans_windows_line_endings() {
+        let source = "// Comment\r\n42\r\n// Last";
+        let tokens = scan_to_tokens(source);
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Comment(" Comment".to_string()),
+                Token::Integer(42),
+                Token::NewLine,
+                Token::Comment(" Last".to_string()),
+                Token::EndOfFile
+            ]
+        )
+    }
+
     #[test]
     fn scans_two_char_tokens() {
         // I threw some spaces in here because my font makes ligatures that can make the tokens a
@@ -62,6 +62,12 @@ pub enum Token {
     CarriageReturn,
 }
 
+// The lexemes of the keyword tokens, for tools like completion in the REPL
+pub const KEYWORDS: &[&str] = &[
+    "and", "class", "else", "false", "fun", "for", "if", "nil", "or", "print", "return", "super",
+    "this", "true", "var", "while",
+];
+
 impl Token {
     pub fn is_whitespace(&self) -> bool {
         matches!(
@@ -69,27 +75,121 @@ impl Token {
             Self::EndOfFile | Self::Space | Self::Tab | Self::NewLine | Self::CarriageReturn
         )
     }
+
+    // Trivia is everything that doesn't change the meaning of the program, but that the scanner
+    // keeps around so that tools can reason about layout. EndOfFile isn't trivia even though it
+    // counts as whitespace.
+    pub fn is_trivia(&self) -> bool {
+        matches!(
+            self,
+            Self::Space | Self::Tab | Self::NewLine | Self::CarriageReturn | Self::Comment(_)
+        )
+    }
+
+    // The name of the variant without any of the value it carries
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::LeftParen => "LeftParen",
+            Self::RightParen => "RightParen",
+            Self::LeftBrace => "LeftBrace",
+            Self::RightBrace => "RightBrace",
+            Self::Comma => "Comma",
+            Self::Dot => "Dot",
+            Self::Minus => "Minus",
+            Self::Plus => "Plus",
+            Self::Semicolon => "Semicolon",
+            Self::Slash => "Slash",
+            Self::ForwardSlash => "ForwardSlash",
+            Self::Star => "Star",
+            Self::Bang => "Bang",
+            Self::BangEqual => "BangEqual",
+            Self::Equal => "Equal",
+            Self::EqualEqual => "EqualEqual",
+            Self::Greater => "Greater",
+            Self::GreaterEqual => "GreaterEqual",
+            Self::Less => "Less",
+            Self::LessEqual => "LessEqual",
+            Self::Colon => "Colon",
+            Self::ColonColon => "ColonColon",
+            Self::Identifier(_) => "Identifier",
+            Self::String(_) => "String",
+            Self::Integer(_) => "Integer",
+            Self::Float(_) => "Float",
+            Self::Boolean(_) => "Boolean",
+            Self::Comment(_) => "Comment",
+            Self::And => "And",
+            Self::Class => "Class",
+            Self::Else => "Else",
+            Self::False => "False",
+            Self::Fun => "Fun",
+            Self::For => "For",
+            Self::If => "If",
+            Self::Nil => "Nil",
+            Self::Or => "Or",
+            Self::Print => "Print",
+            Self::Return => "Return",
+            Self::Super => "Super",
+            Self::This => "This",
+            Self::True => "True",
+            Self::Var => "Var",
+            Self::While => "While",
+            Self::EndOfFile => "EndOfFile",
+            Self::Space => "Space",
+            Self::Tab => "Tab",
+            Self::NewLine => "NewLine",
+            Self::CarriageReturn => "CarriageReturn",
+        }
+    }
 }
 
 // The value and position of the token from the source code
 pub struct TokenContext {
     pub token: Token,
     lexeme: String,
-    // The line the token was found on
+    // The line and column that the token starts at, both counted from 0
     line: usize,
+    column: usize,
     // The start and end grapheme in the source code that the token was at
     start: usize,
     end: usize,
 }
 
 impl TokenContext {
-    pub fn new(token: Token, lexeme: String, line: usize, start: usize, end: usize) -> Self {
+    pub fn new(
+        token: Token,
+        lexeme: String,
+        line: usize,
+        column: usize,
+        start: usize,
+        end: usize,
+    ) -> Self {
         Self {
             token,
             lexeme,
             line,
+            column,
             start,
             end,
         }
     }
+
+    pub fn lexeme(&self) -> &str {
+        &self.lexeme
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn end(&self) -> usize {
+        self.end
+    }
 }
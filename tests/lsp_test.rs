@@ -0,0 +1,193 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Command, Stdio};
+
+use serde_json::{Value, json};
+
+// A scripted client. It sends every message up front, waits for `fellow lsp` to exit and then
+// returns the exit code along with everything the server sent back, in order.
+fn run_session(messages: &[Value]) -> (Option<i32>, Vec<Value>) {
+    let mut server = Command::new(env!("CARGO_BIN_EXE_fellow"))
+        .arg("lsp")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to start fellow lsp");
+
+    let mut stdin = server.stdin.take().unwrap();
+    for message in messages {
+        // Strings are sent as they are, so that tests can send bodies that aren't valid JSON
+        let body = match message {
+            Value::String(raw) => raw.clone(),
+            message => message.to_string(),
+        };
+        write!(stdin, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+    }
+    drop(stdin);
+
+    let mut stdout = BufReader::new(server.stdout.take().unwrap());
+    let mut received = Vec::new();
+    loop {
+        let mut header = String::new();
+        if stdout.read_line(&mut header).unwrap() == 0 {
+            break;
+        }
+        let length: usize = header
+            .trim()
+            .strip_prefix("Content-Length: ")
+            .expect("every message should start with its length")
+            .parse()
+            .unwrap();
+        stdout.read_line(&mut String::new()).unwrap();
+        let mut body = vec![0; length];
+        stdout.read_exact(&mut body).unwrap();
+        received.push(serde_json::from_slice(&body).unwrap());
+    }
+    (server.wait().unwrap().code(), received)
+}
+
+fn request(id: u64, method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+const URI: &str = "file:///solution.fellow";
+
+fn open(text: &str) -> Value {
+    notification(
+        "textDocument/didOpen",
+        json!({
+            "textDocument": { "uri": URI, "languageId": "fellow", "version": 1, "text": text },
+        }),
+    )
+}
+
+fn text_document() -> Value {
+    json!({ "textDocument": { "uri": URI } })
+}
+
+fn session(messages: Vec<Value>) -> (Option<i32>, Vec<Value>) {
+    let mut all = vec![
+        request(0, "initialize", json!({ "capabilities": {} })),
+        notification("initialized", json!({})),
+    ];
+    all.extend(messages);
+    all.push(request(99, "shutdown", Value::Null));
+    all.push(notification("exit", Value::Null));
+    run_session(&all)
+}
+
+fn response(received: &[Value], id: u64) -> &Value {
+    received
+        .iter()
+        .find(|message| message["id"] == id)
+        .unwrap_or_else(|| panic!("no response to request {}", id))
+}
+
+fn diagnostics(received: &[Value]) -> Vec<&Value> {
+    received
+        .iter()
+        .filter(|message| message["method"] == "textDocument/publishDiagnostics")
+        .map(|message| &message["params"]["diagnostics"])
+        .collect()
+}
+
+#[test]
+fn advertises_what_the_scanner_can_support() {
+    let (code, received) = session(Vec::new());
+    assert_eq!(code, Some(0));
+    let capabilities = &response(&received, 0)["result"]["capabilities"];
+    assert_eq!(capabilities["textDocumentSync"], 1);
+    assert_eq!(capabilities["documentFormattingProvider"], true);
+    assert!(capabilities["semanticTokensProvider"]["legend"].is_object());
+    assert!(capabilities.get("hoverProvider").is_none());
+    assert_eq!(response(&received, 99)["result"], Value::Null);
+}
+
+#[test]
+fn publishes_diagnostics_when_a_document_changes() {
+    let change = notification(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": URI, "version": 2 },
+            "contentChanges": [{ "text": "1\n\"fixed\"\n" }],
+        }),
+    );
+    let (_, received) = session(vec![open("1\n\"open\n"), change]);
+    let diagnostics = diagnostics(&received);
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(diagnostics[0][0]["code"], "F0102");
+    assert_eq!(
+        diagnostics[0][0]["range"],
+        json!({
+            "start": { "line": 1, "character": 0 },
+            "end": { "line": 1, "character": 1 },
+        })
+    );
+    assert_eq!(diagnostics[1], &json!([]));
+}
+
+#[test]
+fn highlights_and_formats_documents() {
+    let (_, received) = session(vec![
+        open("{\n1+2\n}\n"),
+        request(1, "textDocument/semanticTokens/full", text_document()),
+        request(
+            2,
+            "textDocument/formatting",
+            json!({
+                "textDocument": { "uri": URI },
+                "options": { "tabSize": 4, "insertSpaces": true },
+            }),
+        ),
+    ]);
+    assert_eq!(
+        response(&received, 1)["result"]["data"],
+        json!([1, 0, 1, 3, 0, 0, 1, 1, 4, 0, 0, 1, 1, 3, 0])
+    );
+    assert_eq!(
+        response(&received, 2)["result"],
+        json!([{
+            "range": {
+                "start": { "line": 0, "character": 0 },
+                "end": { "line": 3, "character": 0 },
+            },
+            "newText": "{\n    1 + 2\n}\n",
+        }])
+    );
+}
+
+#[test]
+fn rejects_unsupported_requests() {
+    let hover = json!({
+        "textDocument": { "uri": URI },
+        "position": { "line": 0, "character": 0 },
+    });
+    let (_, received) = session(vec![open("1\n"), request(1, "textDocument/hover", hover)]);
+    assert_eq!(response(&received, 1)["error"]["code"], -32601);
+}
+
+#[test]
+fn keeps_serving_after_invalid_messages() {
+    let (code, received) = run_session(&[
+        json!("{bad}"),
+        request(0, "initialize", json!({ "capabilities": {} })),
+        // A response from the client to a request that the server never made
+        json!({ "jsonrpc": "2.0", "id": 7, "result": null }),
+        request(1, "shutdown", Value::Null),
+        notification("exit", Value::Null),
+    ]);
+    assert_eq!(code, Some(0));
+    assert_eq!(received[0]["error"]["code"], -32700);
+    assert_eq!(received[0]["id"], Value::Null);
+    assert!(received.iter().all(|message| message["id"] != 7));
+    assert_eq!(response(&received, 1)["result"], Value::Null);
+}
+
+#[test]
+fn exits_with_an_error_without_a_shutdown() {
+    let (code, _) = run_session(&[notification("exit", Value::Null)]);
+    assert_eq!(code, Some(1));
+}
@@ -1,54 +1,199 @@
+use std::env;
 use std::fs;
+use std::io::{Write, stderr};
 use std::path::Path;
 
-use fellow::interpret;
+use fellow::{FellowError, run};
+
+// The expectations for a script are written as line comments anywhere in the file. They're
+// blanked out before the script runs, so they don't count as its last value:
+//
+// * `// expect: <line>` - one line of stdout. Repeat it for programs that print several lines.
+// * `// expected: <line>` - the original single-line header, treated the same as `// expect:`.
+// * `// expect-error: <code>` - the script must fail with the FellowError that has this code.
+// * `// expect-runtime-error` - the script must fail while running rather than while scanning.
+#[derive(Debug, Default)]
+struct Expectations {
+    stdout: Vec<String>,
+    error_code: Option<String>,
+    runtime_error: bool,
+}
+
+impl Expectations {
+    fn is_empty(&self) -> bool {
+        self.stdout.is_empty() && self.error_code.is_none() && !self.runtime_error
+    }
+}
+
+fn parse_expectations(script_content: &str) -> Expectations {
+    let mut expectations = Expectations::default();
+    for line in script_content.lines().map(str::trim_start) {
+        if let Some(output) = line
+            .strip_prefix("// expect:")
+            .or_else(|| line.strip_prefix("// expected:"))
+        {
+            // Only the single space after the colon is part of the header, so that expected
+            // output can still start with whitespace.
+            let output = output.strip_prefix(' ').unwrap_or(output);
+            expectations.stdout.push(output.trim_end().to_string());
+        } else if let Some(code) = line.strip_prefix("// expect-error:") {
+            expectations.error_code = Some(code.trim().to_string());
+        } else if line.trim_end() == "// expect-runtime-error" {
+            expectations.runtime_error = true;
+        }
+    }
+    expectations
+}
+
+fn is_expectation(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with("// expect:")
+        || line.starts_with("// expected:")
+        || line.starts_with("// expect-error:")
+        || line.trim_end() == "// expect-runtime-error"
+}
+
+// The script with each expectation replaced by an empty line. Comments are tokens, so an
+// expectation after the code would otherwise become the value of the script. The lines stay
+// where they were so that errors point at the right place.
+fn without_expectations(script_content: &str) -> String {
+    script_content
+        .split_inclusive('\n')
+        .map(|line| {
+            if is_expectation(line) {
+                // Keep just the line ending
+                &line[line.trim_end_matches(['\r', '\n']).len()..]
+            } else {
+                line
+            }
+        })
+        .collect()
+}
+
+// Compares what a script did against its expectations, describing the first difference.
+fn mismatch(
+    expectations: &Expectations,
+    result: &Result<(), FellowError>,
+    actual_output: &[String],
+) -> Option<String> {
+    match (result, &expectations.error_code, expectations.runtime_error) {
+        (Ok(()), None, false) => {}
+        (Ok(()), _, _) => return Some("succeeded but was expected to fail".to_string()),
+        (Err(err), Some(code), _) => {
+            if err.code() != code {
+                return Some(format!(
+                    "expected error {} but failed with {}: {}",
+                    code,
+                    err.code(),
+                    err
+                ));
+            }
+        }
+        (Err(err), None, true) => {
+            if !matches!(err, FellowError::Runtime(_)) {
+                return Some(format!(
+                    "was expected to fail at runtime, but failed with: {}",
+                    err
+                ));
+            }
+        }
+        (Err(err), None, false) => return Some(format!("Interpreter Error: {:?}", err)),
+    }
+    if actual_output != expectations.stdout {
+        return Some(format!(
+            "stdout mismatch\n  expected: {:?}\n    actual: {:?}",
+            expectations.stdout, actual_output
+        ));
+    }
+    None
+}
+
+// Replaces the expectation lines of a script with ones describing what it actually did. The new
+// lines go where the first old one was, or at the top of the file if there weren't any.
+fn bless(
+    script_content: &str,
+    expectations: &Expectations,
+    result: &Result<(), FellowError>,
+    actual_output: &[String],
+) -> String {
+    let mut blessed: Vec<String> = actual_output
+        .iter()
+        .map(|line| format!("// expect: {}", line).trim_end().to_string())
+        .collect();
+    match result {
+        // Keep the looser runtime expectation if it still holds
+        Err(FellowError::Runtime(_))
+            if expectations.runtime_error && expectations.error_code.is_none() =>
+        {
+            blessed.push("// expect-runtime-error".to_string())
+        }
+        Err(err) => blessed.push(format!("// expect-error: {}", err.code())),
+        Ok(()) => {}
+    }
+
+    let lines: Vec<&str> = script_content.lines().collect();
+    let insert_at = lines
+        .iter()
+        .position(|line| is_expectation(line))
+        .unwrap_or(0);
+    let mut output: Vec<String> = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        if i == insert_at {
+            output.append(&mut blessed);
+        }
+        if !is_expectation(line) {
+            output.push(line.to_string());
+        }
+    }
+    output.append(&mut blessed);
+
+    let mut new_content = output.join("\n");
+    if script_content.ends_with('\n') {
+        new_content.push('\n');
+    }
+    new_content
+}
 
 // This is the helper function that runs the test logic for a single script.
 // It needs to be public so the generated test functions can call it.
+//
+// Setting FELLOW_BLESS=1 rewrites the expectation lines of any script that doesn't match instead
+// of failing the test.
 pub fn run_single_script_test(script_path: &Path) {
     let script_content = fs::read_to_string(script_path)
         .unwrap_or_else(|_| panic!("Failed to read script file: {}", script_path.display()));
+    let blessing = env::var("FELLOW_BLESS").is_ok_and(|value| value != "0");
 
-    // Extract the expected output from the first line comment
-    let mut lines = script_content.lines();
-    let first_line = lines
-        .next()
-        .unwrap_or_else(|| panic!("Script file is empty: {}", script_path.display()));
-
-    let expected_prefix = "// expected: ";
-    let expected_output = if let Some(output) = first_line.strip_prefix(expected_prefix) {
-        output.trim().to_string()
-    } else {
-        // If the first line doesn't have the expected format, panic the test
+    let expectations = parse_expectations(&script_content);
+    if expectations.is_empty() && !blessing {
         panic!(
-            "Error: First line of {} does not start with '{}'",
-            script_path.display(),
-            expected_prefix
+            "Error: {} has no `// expect:`, `// expect-error:` or `// expect-runtime-error` lines",
+            script_path.display()
         );
-    };
+    }
 
-    let actual_output = match interpret(&script_content) {
-        Ok(output) => format!("{}", output),
-        Err(err) => {
-            // If your interpreter returns an error, treat it as an interpretation failure
-            // and potentially include the error message in the "actual output" for assertion,
-            // or panic directly. Panicking is simpler for tests.
-            panic!(
-                "Interpreter Error running script {}: {:?}",
-                script_path.display(),
-                err
-            );
-        }
+    // Anything the script prints before failing is still compared against the expected output.
+    let mut stdout = Vec::new();
+    let result = run(&without_expectations(&script_content), &mut stdout);
+    let actual_output: Vec<String> = String::from_utf8_lossy(&stdout)
+        .lines()
+        .map(|line| line.trim_end().to_string())
+        .collect();
+
+    let Some(problem) = mismatch(&expectations, &result, &actual_output) else {
+        return;
     };
+    if !blessing {
+        panic!("Mismatch for script {}: {}", script_path.display(), problem);
+    }
 
-    // Compare actual output with expected output
-    // Use assert_eq! directly; it will cause the calling #[test] function to fail
-    assert_eq!(
-        actual_output,
-        expected_output,
-        "Mismatch for script: {}",
-        script_path.display()
-    );
+    let blessed = bless(&script_content, &expectations, &result, &actual_output);
+    fs::write(script_path, blessed)
+        .unwrap_or_else(|_| panic!("Failed to bless script file: {}", script_path.display()));
+    // The test harness captures print! and eprint!, but not direct writes to stderr. Writing
+    // directly means the list of blessed scripts shows up without --nocapture.
+    writeln!(stderr(), "blessed {}: {}", script_path.display(), problem)
+        .expect("Failed to write to stderr");
 }
 
 // Include the generated test functions.